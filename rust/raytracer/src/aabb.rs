@@ -0,0 +1,79 @@
+use super::vec::Point3;
+use super::ray::Ray;
+
+// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    minimum: Point3,
+    maximum: Point3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Point3, maximum: Point3) -> Aabb {
+        Aabb {
+            minimum,
+            maximum,
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.maximum
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.minimum + self.maximum) / 2.0
+    }
+
+    // The smallest box containing both boxes.
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for axis in 0..3 {
+            minimum[axis] = minimum[axis].min(other.minimum[axis]);
+            maximum[axis] = maximum[axis].max(other.maximum[axis]);
+        }
+        Aabb::new(minimum, maximum)
+    }
+
+    // Flat things, like triangles lying in an axis-aligned plane, have boxes
+    // with no thickness at all, which rays can slip past due to rounding.
+    // This pads out any side thinner than delta.
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut minimum = self.minimum;
+        let mut maximum = self.maximum;
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < delta {
+                minimum[axis] -= delta / 2.0;
+                maximum[axis] += delta / 2.0;
+            }
+        }
+        Aabb::new(minimum, maximum)
+    }
+
+    // The slab method: intersect the ray with the pair of planes bounding the
+    // box on each axis, and see if all three of those intervals overlap.
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction()[axis];
+            let mut t0 = (self.minimum[axis] - r.origin()[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - r.origin()[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // f64::max and f64::min ignore NaNs, which show up when the ray is
+            // parallel to and exactly on one of the planes. Ignoring that
+            // axis is the conservative thing to do.
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,189 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::image_reader::HdrImage;
+use super::ray::Ray;
+use super::vec::Color;
+
+// What rays that don't hit anything see. Since it's also where those rays'
+// light comes from, the background lights the whole scene.
+#[typetag::serde(tag = "type")]
+pub trait Background: Send + Sync {
+    fn color(&self, r: &Ray) -> Color;
+}
+
+// Shown the way it's written in a scene file, so things holding a background
+// (like the Config) can still be printed with {:?}.
+impl fmt::Debug for dyn Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    #[allow(unused)]
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor {
+            color,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Background for SolidColor {
+    fn color(&self, _r: &Ray) -> Color {
+        self.color
+    }
+}
+
+// No light at all, for scenes lit only by the things in them.
+#[derive(Serialize, Deserialize)]
+pub struct Black {}
+
+#[typetag::serde]
+impl Background for Black {
+    fn color(&self, _r: &Ray) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
+// Blends from bottom (looking straight down) to top (looking straight up).
+// The default is the sky from the tutorial.
+#[derive(Serialize, Deserialize)]
+pub struct VerticalGradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl VerticalGradient {
+    #[allow(unused)]
+    pub fn new(bottom: Color, top: Color) -> VerticalGradient {
+        VerticalGradient {
+            bottom,
+            top,
+        }
+    }
+}
+
+impl Default for VerticalGradient {
+    fn default() -> VerticalGradient {
+        VerticalGradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+#[typetag::serde]
+impl Background for VerticalGradient {
+    // Because the ray is normalized first, there is a slight horizontal
+    // gradient too, from the top color on the left, through the bottom color,
+    // and back to the top color on the right. Basically, the x stole from the
+    // y when it was pointing left and pointing right. This is why the image is
+    // pretty :).
+    fn color(&self, r: &Ray) -> Color {
+        let unit_direction = r.direction().normalized();
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+// What an EnvironmentMap looks like in a scene file.
+#[derive(Clone, Serialize, Deserialize)]
+struct EnvironmentMapSettings {
+    // A Radiance (.hdr) or OpenEXR (.exr) image, in the equirectangular
+    // (latitude-longitude) layout. Relative paths are relative to the
+    // directory the raytracer is run from, same as world_path.
+    path: PathBuf,
+    // In degrees, around the vertical axis.
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "default_intensity")]
+    intensity: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+// A panoramic image of everything around the scene, for image-based lighting.
+// It's laid out the same way as what the equirectangular camera renders, so
+// the middle of the image is straight ahead of the default camera (along -z),
+// the left and right edges are behind it, and the top and bottom rows are
+// straight up and straight down. The image gets loaded along with the scene.
+#[derive(Deserialize)]
+#[serde(try_from = "EnvironmentMapSettings")]
+pub struct EnvironmentMap {
+    settings: EnvironmentMapSettings,
+    image: Arc<HdrImage>,
+}
+
+impl TryFrom<EnvironmentMapSettings> for EnvironmentMap {
+    type Error = io::Error;
+
+    fn try_from(settings: EnvironmentMapSettings) -> io::Result<EnvironmentMap> {
+        let image = HdrImage::load(&settings.path)?;
+        if image.width == 0 || image.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: The environment map is empty.", settings.path.display()),
+            ));
+        }
+        Ok(EnvironmentMap {
+            settings,
+            image: Arc::new(image),
+        })
+    }
+}
+
+// Written back out the way it was read in, as a path, not as pixels.
+impl Serialize for EnvironmentMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.settings.serialize(serializer)
+    }
+}
+
+impl EnvironmentMap {
+    // Bilinearly filtered, wrapping around horizontally. x and y are in
+    // pixels, with pixel centers at the halves.
+    fn sample(&self, x: f64, y: f64) -> Color {
+        let image = &self.image;
+        let x = x - 0.5;
+        let y = (y - 0.5).clamp(0.0, (image.height - 1) as f64);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let column = |x: f64| (x as i64).rem_euclid(image.width as i64) as usize;
+        let (left, right) = (column(x0), column(x0 + 1.0));
+        let top = y0 as usize;
+        let bottom = (top + 1).min(image.height - 1);
+
+        let upper = (1.0 - tx) * image.get(left, top) + tx * image.get(right, top);
+        let lower = (1.0 - tx) * image.get(left, bottom) + tx * image.get(right, bottom);
+        (1.0 - ty) * upper + ty * lower
+    }
+}
+
+#[typetag::serde]
+impl Background for EnvironmentMap {
+    fn color(&self, r: &Ray) -> Color {
+        let direction = r.direction().normalized();
+        let longitude = direction.x().atan2(-direction.z()) - self.settings.rotation.to_radians();
+        let latitude = direction.y().clamp(-1.0, 1.0).asin();
+
+        let u = (longitude / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = 0.5 - latitude / PI;
+        self.settings.intensity * self.sample(u * self.image.width as f64, v * self.image.height as f64)
+    }
+}
@@ -1,10 +1,10 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use clap_serde_derive::clap::{self, Parser};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha12Rng;
 use skean_raytracer::{
-    hit::World, material::{Lambertian, Material, Metal}, plane::Plane, sphere::Sphere, vec::{Color, Point3, Vec3}
+    bvh::BvhWorld, camera::CameraSettings, cuboid::Cuboid, disk::Disk, hit::{Hit, World}, material::{Dielectric, Lambertian, Material, Metal}, plane::Plane, quad::Quad, scene::{default_background, NamedObjects, Scene}, sphere::Sphere, vec::{Color, Point3, Vec3}
 };
 
 #[derive(Parser)]
@@ -21,8 +21,21 @@ struct Cli {
     /// How many planes to generate.
     #[arg(short = 'P', long, default_value_t = 5)]
     num_planes: u64,
-    /// Probability, between 0 and 1, that a given object is metallic. The
-    /// alternative is that it is diffuse.
+    /// How many quads (parallelograms) to generate, among the small spheres.
+    #[arg(short = 'Q', long, default_value_t = 0)]
+    num_quads: u64,
+    /// How many disks to generate, among the small spheres.
+    #[arg(short = 'D', long, default_value_t = 0)]
+    num_disks: u64,
+    /// How many axis-aligned boxes to generate, among the small spheres.
+    #[arg(short = 'B', long, default_value_t = 0)]
+    num_boxes: u64,
+    /// Probability, between 0 and 1, that a given object is glass. Objects
+    /// that aren't glass are then metallic or diffuse.
+    #[arg(short = 'g', long, default_value_t = 0.0)]
+    glass_probability: f64,
+    /// Probability, between 0 and 1, that a given object that isn't glass is
+    /// metallic. The alternative is that it is diffuse.
     #[arg(short = 'm', long, default_value_t = 0.6)]
     metallic_probability: f64,
     // Probability, between 0 and 1, that a diffuse material is emissive.
@@ -36,29 +49,73 @@ struct Cli {
     /// between 15.0 and 20.0).
     #[arg(short = 's', long, default_value_t = 0.9)]
     small_sphere_probability: f64,
+    /// Vertical field of view of the camera that frames the generated scene,
+    /// in degrees.
+    #[arg(long = "vfov", default_value_t = 40.0)]
+    vertical_fov: f64,
 }
 
-fn gen_material(options: &Cli, rng: &mut impl Rng) -> Rc<dyn Material> {
+fn gen_material(options: &Cli, rng: &mut impl Rng) -> Arc<dyn Material> {
     let rand_color = Color::new(rng.gen(), rng.gen(), rng.gen());
-    let rand_mat: Rc<dyn Material> = if rng.gen_bool(options.metallic_probability) {
+    // Checking the probability first means that without any glass, we don't
+    // draw an extra number from the rng, so old seeds still generate the same
+    // scenes they used to.
+    let rand_mat: Arc<dyn Material> = if options.glass_probability > 0.0 && rng.gen_bool(options.glass_probability) {
+        Arc::new(Dielectric::new(rng.gen_range(1.3..1.8)))
+    } else if rng.gen_bool(options.metallic_probability) {
         if rng.gen_bool(options.emissive_probability_metallic) {
             let rand_emission = Color::new(rng.gen(), rng.gen(), rng.gen());
-            Rc::new(Metal::new_emissive(rand_color, rng.gen(), rand_emission))
+            Arc::new(Metal::new_emissive(rand_color, rng.gen(), rand_emission))
         } else {
-            Rc::new(Metal::new(rand_color, rng.gen()))
+            Arc::new(Metal::new(rand_color, rng.gen()))
         }
     } else {
         if rng.gen_bool(options.emissive_probability_diffuse) {
             let rand_emission = Color::new(rng.gen(), rng.gen(), rng.gen());
-            Rc::new(Lambertian::new_emissive(rand_color, rand_emission))
+            Arc::new(Lambertian::new_emissive(rand_color, rand_emission))
         } else {
-            Rc::new(Lambertian::new(rand_color))
+            Arc::new(Lambertian::new(rand_color))
         }
     };
 
     rand_mat
 }
 
+// Aims the camera at the middle of the box bounding all the finite objects,
+// and backs it off along +Z until the sphere around that box fits in the field
+// of view. The planes go on forever, so they don't get a say.
+fn framing_camera(options: &Cli, lower: Point3, upper: Point3) -> CameraSettings {
+    let center = (lower + upper) / 2.0;
+    let radius = (upper - lower).length() / 2.0;
+    let distance = radius / (options.vertical_fov.to_radians() / 2.0).sin();
+
+    CameraSettings {
+        look_from: Some(center + Vec3::new(0.0, 0.0, distance)),
+        look_at: Some(center),
+        vertical_fov: Some(options.vertical_fov),
+        ..CameraSettings::default()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ShapeKind {
+    Quad,
+    Disk,
+    Box,
+}
+
+// A shape of about the same size as the small spheres, in the same spot.
+fn gen_shape(kind: ShapeKind, rng: &mut impl Rng, mat: Arc<dyn Material>) -> Box<dyn Hit> {
+    let position = Point3::new(rng.gen_range(-2.0..2.0), rng.gen_range(-0.5..1.0), rng.gen_range(-2.0..-1.0));
+    match kind {
+        ShapeKind::Quad => Box::new(Quad::new(position, Vec3::random(rng, -0.5..0.5), Vec3::random(rng, -0.5..0.5), mat)),
+        ShapeKind::Disk => Box::new(Disk::new(position, Vec3::random(rng, -1.0..1.0), rng.gen_range(0.05..0.4), mat)),
+        ShapeKind::Box => {
+            Box::new(Cuboid::new([position, position + Vec3::random(rng, 0.1..0.6)], mat).expect("Generated boxes all have some size."))
+        }
+    }
+}
+
 fn main() {
 
     // TODO: There's gotta be a cleaner way to do this!! With less redundant code between things.
@@ -66,32 +123,70 @@ fn main() {
     let options = Cli::parse();
     let mut world = World::new();
 
+    // Corners of the box bounding all the spheres (and other finite shapes)
+    // so far.
+    let mut lower = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut upper = Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
     let mut rng = ChaCha12Rng::seed_from_u64(options.random_seed);
+
+    // The quads, disks and boxes go first, so the spheres can be kept out of
+    // them with collides_with_sphere. They're kept out of each other by the
+    // spheres around their bounding boxes, which is overly cautious, but
+    // that's all the collision checking there is.
+    let shape_kinds = [
+        (options.num_quads, ShapeKind::Quad),
+        (options.num_disks, ShapeKind::Disk),
+        (options.num_boxes, ShapeKind::Box),
+    ];
+    for (count, kind) in shape_kinds {
+        for _ in 0..count {
+            'getting_a_good_shape: loop {
+                let rand_mat = gen_material(&options, &mut rng);
+                let shape = gen_shape(kind, &mut rng, rand_mat.clone());
+                let bbox = shape.bounding_box().expect("Generated shapes are all finite.");
+                if !options.allow_collision {
+                    let bounding_sphere = Sphere::new(bbox.centroid(), (bbox.max() - bbox.min()).length() / 2.0, rand_mat);
+                    for hit in world.iter() {
+                        if hit.collides_with_sphere(&bounding_sphere) {
+                            continue 'getting_a_good_shape;
+                        }
+                    }
+                }
+                world.push(shape);
+                for axis in 0..3 {
+                    lower[axis] = lower[axis].min(bbox.min()[axis]);
+                    upper[axis] = upper[axis].max(bbox.max()[axis]);
+                }
+                break 'getting_a_good_shape;
+            }
+        }
+    }
+
     for _ in 0..options.num_spheres {
         'getting_a_good_sphere: loop {
             let rand_mat = gen_material(&options, &mut rng);
 
-            let sphere = if rng.gen_bool(options.small_sphere_probability) {
-                Sphere::new(
+            let (center, radius) = if rng.gen_bool(options.small_sphere_probability) {
+                (
                     Point3::new(
                         rng.gen_range(-2.0..2.0),
                         rng.gen_range(-0.5..1.0),
                         rng.gen_range(-2.0..-1.0),
                     ),
                     rng.gen_range(0.0..0.4),
-                    rand_mat,
                 )
             } else {
-                Sphere::new(
+                (
                     Point3::new(
                         rng.gen_range(-50.0..50.0),
                         rng.gen_range(-50.0..50.0),
                         rng.gen_range(-50.0..-25.0),
                     ),
                     rng.gen_range(15.0..20.0),
-                    rand_mat,
                 )
             };
+            let sphere = Sphere::new(center, radius, rand_mat);
             if !options.allow_collision {
                 for hit in world.iter() {
                     if hit.collides_with_sphere(&sphere) {
@@ -100,6 +195,10 @@ fn main() {
                 }
             }
             world.push(Box::new(sphere));
+            for axis in 0..3 {
+                lower[axis] = lower[axis].min(center[axis] - radius);
+                upper[axis] = upper[axis].max(center[axis] + radius);
+            }
             break 'getting_a_good_sphere;
         }
     }
@@ -123,5 +222,19 @@ fn main() {
         world.push(Box::new(plane));
     }
 
-    serde_json::to_writer_pretty(std::io::stdout(), &world).expect("Unable to write to standard out.");
+    let camera = if options.num_spheres + options.num_quads + options.num_disks + options.num_boxes > 0 {
+        framing_camera(&options, lower, upper)
+    } else {
+        CameraSettings::default()
+    };
+
+    let scene = Scene {
+        camera,
+        named_objects: NamedObjects::default(),
+        objects: BvhWorld::new(world),
+        background: default_background(),
+        lights: Vec::new(),
+    };
+
+    serde_json::to_writer_pretty(std::io::stdout(), &scene).expect("Unable to write to standard out.");
 }
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord, World};
+use super::ray::Ray;
+
+enum BvhNode {
+    Leaf {
+        bbox: Aabb,
+        index: usize,
+    },
+    Interior {
+        bbox: Aabb,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+impl BvhNode {
+    fn bbox(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } => bbox,
+            BvhNode::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+// A bounding volume hierarchy over anything with bounding boxes. It only knows
+// about the boxes and their indices, not the objects themselves, so the same
+// tree works for a World and for anything else made of many pieces.
+pub struct Bvh {
+    root: Option<BvhNode>,
+}
+
+impl Bvh {
+    // The objects being indexed are the ones whose bounding boxes are in
+    // boxes, in the same order. indices says which of those to put in the
+    // tree; the rest are left out.
+    pub fn new(boxes: &[Aabb], mut indices: Vec<usize>) -> Bvh {
+        Bvh {
+            root: if indices.is_empty() {
+                None
+            } else {
+                Some(Bvh::build(boxes, &mut indices))
+            },
+        }
+    }
+
+    // Splits the objects in half at the median along whichever axis their
+    // centroids are most spread out on. indices must not be empty.
+    fn build(boxes: &[Aabb], indices: &mut [usize]) -> BvhNode {
+        if let [index] = *indices {
+            return BvhNode::Leaf {
+                bbox: boxes[index],
+                index,
+            };
+        }
+
+        let mut centroid_min = boxes[indices[0]].centroid();
+        let mut centroid_max = centroid_min;
+        for &index in indices.iter() {
+            let centroid = boxes[index].centroid();
+            for axis in 0..3 {
+                centroid_min[axis] = centroid_min[axis].min(centroid[axis]);
+                centroid_max[axis] = centroid_max[axis].max(centroid[axis]);
+            }
+        }
+        let extent = centroid_max - centroid_min;
+        let axis = (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap();
+
+        let mid = indices.len() / 2;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            boxes[a].centroid()[axis].total_cmp(&boxes[b].centroid()[axis])
+        });
+        let (left_indices, right_indices) = indices.split_at_mut(mid);
+
+        let left = Bvh::build(boxes, left_indices);
+        let right = Bvh::build(boxes, right_indices);
+
+        BvhNode::Interior {
+            bbox: left.bbox().surrounding(right.bbox()),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    // Finds the closest hit, calling hit_object(index, r, t_min, t_max) for
+    // each object whose bounding box the ray passes through. closest is the
+    // closest hit so far (if any) along with its object's index, so the
+    // search can pick up where another one left off.
+    //
+    // Ties go to the object with the larger index, which is what testing the
+    // objects in order with closest_so_far as t_max does. That keeps images
+    // exactly the same as without the tree, even for coincident surfaces.
+    pub fn hit<'a, F>(&self, r: &Ray, t_min: f64, t_max: f64, closest: Option<(usize, HitRecord<'a>)>, hit_object: &F) -> Option<(usize, HitRecord<'a>)>
+    where
+        F: Fn(usize, &Ray, f64, f64) -> Option<HitRecord<'a>>,
+    {
+        match &self.root {
+            Some(root) => Bvh::hit_node(root, r, t_min, t_max, closest, hit_object),
+            None => closest,
+        }
+    }
+
+    fn hit_node<'a, F>(node: &BvhNode, r: &Ray, t_min: f64, t_max: f64, closest: Option<(usize, HitRecord<'a>)>, hit_object: &F) -> Option<(usize, HitRecord<'a>)>
+    where
+        F: Fn(usize, &Ray, f64, f64) -> Option<HitRecord<'a>>,
+    {
+        let closest_so_far = closest.as_ref().map_or(t_max, |(_, rec)| rec.t);
+        if !node.bbox().hit(r, t_min, closest_so_far) {
+            return closest;
+        }
+
+        match node {
+            BvhNode::Leaf { index, .. } => match hit_object(*index, r, t_min, closest_so_far) {
+                Some(rec) => match closest {
+                    Some((closest_index, closest_rec)) if rec.t == closest_rec.t && *index < closest_index => {
+                        Some((closest_index, closest_rec))
+                    }
+                    _ => Some((*index, rec)),
+                },
+                None => closest,
+            },
+            BvhNode::Interior { left, right, .. } => {
+                let closest = Bvh::hit_node(left, r, t_min, t_max, closest, hit_object);
+                Bvh::hit_node(right, r, t_min, t_max, closest, hit_object)
+            }
+        }
+    }
+}
+
+// A World, with a Bvh over all of its objects that have bounding boxes.
+// Objects without them, like Planes, which go on forever, can't go in the
+// tree, so every ray is just tested against each of them too. Scenes
+// shouldn't have many of those.
+//
+// It also keeps track of which objects are lights (see Hit::is_light), so
+// they can be sampled.
+//
+// This serializes and deserializes exactly like the World inside it, building
+// the tree as it's deserialized.
+pub struct BvhWorld {
+    objects: World,
+    bvh: Bvh,
+    unbounded: Vec<usize>,
+    lights: Vec<usize>,
+}
+
+impl BvhWorld {
+    pub fn new(objects: World) -> BvhWorld {
+        let mut boxes = Vec::with_capacity(objects.len());
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut lights = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            if object.is_light() {
+                lights.push(index);
+            }
+            match object.bounding_box() {
+                Some(bbox) => {
+                    boxes.push(bbox);
+                    bounded.push(index);
+                }
+                None => {
+                    // Never looked at, since this index isn't in the tree.
+                    boxes.push(Aabb::new(Default::default(), Default::default()));
+                    unbounded.push(index);
+                }
+            }
+        }
+
+        BvhWorld {
+            bvh: Bvh::new(&boxes, bounded),
+            objects,
+            unbounded,
+            lights,
+        }
+    }
+
+    pub fn object(&self, index: usize) -> &dyn Hit {
+        self.objects[index].as_ref()
+    }
+
+    // The indices of the objects that are lights.
+    pub fn lights(&self) -> &[usize] {
+        &self.lights
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.hit_with_index(r, t_min, t_max).map(|(_, rec)| rec)
+    }
+
+    // Like hit, but also says which object was hit.
+    pub fn hit_with_index(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, HitRecord<'_>)> {
+        let hit_object = |index: usize, r: &Ray, t_min: f64, t_max: f64| {
+            self.objects[index].hit(r, t_min, t_max)
+        };
+
+        let mut closest = None;
+        for &index in &self.unbounded {
+            let closest_so_far = closest.as_ref().map_or(t_max, |(_, rec): &(usize, HitRecord)| rec.t);
+            if let Some(rec) = hit_object(index, r, t_min, closest_so_far) {
+                closest = Some((index, rec));
+            }
+        }
+
+        self.bvh.hit(r, t_min, t_max, closest, &hit_object)
+    }
+}
+
+impl Serialize for BvhWorld {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.objects.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BvhWorld {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BvhWorld, D::Error> {
+        World::deserialize(deserializer).map(BvhWorld::new)
+    }
+}
@@ -1,36 +1,183 @@
+use std::f64::consts::PI;
+
+use clap_serde_derive::clap::{self, ValueEnum};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
 use super::vec::{Point3, Vec3};
 use super::ray::Ray;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection {
+    // The usual pinhole (or thin lens) camera.
+    #[default]
+    Perspective,
+    // All rays are parallel to the viewing direction, so things don't get
+    // smaller with distance. The viewport is the same size as the perspective
+    // camera's would be at the focus distance, so both frame the focus plane
+    // the same way. Ignores the aperture.
+    Orthographic,
+    // A full 360x180 degree panorama around look_from, with look_at in the
+    // middle of the image. Ignores the field of view and the aperture, and
+    // should be rendered at a 2:1 aspect ratio.
+    Equirectangular,
+}
+
+// How a scene file describes its camera. Everything is optional, so that the
+// config and the command line can override individual settings. See
+// CameraSettings::to_camera for what the defaults are.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub look_from: Option<Point3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub look_at: Option<Point3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vup: Option<Vec3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vertical_fov: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aperture: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_dist: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projection: Option<Projection>,
+}
+
+impl CameraSettings {
+    // Fills in every setting that isn't specified here with the one from
+    // fallback.
+    pub fn or(self, fallback: &CameraSettings) -> CameraSettings {
+        CameraSettings {
+            look_from: self.look_from.or(fallback.look_from),
+            look_at: self.look_at.or(fallback.look_at),
+            vup: self.vup.or(fallback.vup),
+            vertical_fov: self.vertical_fov.or(fallback.vertical_fov),
+            aperture: self.aperture.or(fallback.aperture),
+            focus_dist: self.focus_dist.or(fallback.focus_dist),
+            projection: self.projection.or(fallback.projection),
+        }
+    }
+
+    // These defaults put the camera exactly where it used to be fixed: at the
+    // origin, looking down -Z, with a viewport 2.0 high at a focal length of
+    // 1.0, and with everything in focus.
+    pub fn to_camera(&self, aspect_ratio: f64) -> Camera {
+        let look_from = self.look_from.unwrap_or(Point3::new(0.0, 0.0, 0.0));
+        let look_at = self.look_at.unwrap_or(Point3::new(0.0, 0.0, -1.0));
+        Camera::new(
+            look_from,
+            look_at,
+            self.vup.unwrap_or(Vec3::new(0.0, 1.0, 0.0)),
+            self.vertical_fov.unwrap_or(90.0),
+            aspect_ratio,
+            self.aperture.unwrap_or(0.0),
+            self.focus_dist.unwrap_or((look_from - look_at).length()),
+            self.projection.unwrap_or_default(),
+        )
+    }
+}
+
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
-    vertical: Vec3
+    vertical: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    lens_radius: f64,
+    focus_dist: f64,
+    projection: Projection,
 }
 
 impl Camera {
-    pub fn new(aspect_ratio: f64) -> Camera {
-        const VIEWPORT_HEIGHT: f64 = 2.0;
-        let viewport_width: f64 = aspect_ratio * VIEWPORT_HEIGHT;
-        const FOCAL_LENGTH: f64 = 1.0;
+    // vertical_fov is in degrees, measured from the top edge of the viewport
+    // to the bottom edge. vup only needs to be roughly "up"; it gets projected
+    // onto the plane of the viewport, so it just can't be parallel to the
+    // viewing direction.
+    //
+    // aperture is the diameter of the lens. Everything at focus_dist from
+    // look_from is perfectly in focus, and things get blurrier the further
+    // they are from that plane. An aperture of 0.0 is a pinhole camera, where
+    // everything is in focus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        vertical_fov: f64,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
+        projection: Projection,
+    ) -> Camera {
+        let theta = vertical_fov.to_radians();
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
 
-        let origin = Point3::new(0.0, 0.0, 0.0);
-        let horizontal = Vec3::new(viewport_width, 0.0, 0.0);
-        let vertical = Vec3::new(0.0, VIEWPORT_HEIGHT, 0.0);
-        let lower_left_corner =
-            origin - horizontal / 2.0 - vertical / 2.0 - Vec3::new(0.0, 0.0, FOCAL_LENGTH);
+        // An orthonormal basis for the camera. Like in the original camera, we
+        // look down -w, so w points backwards out of the viewport.
+        let w = (look_from - look_at).normalized();
+        let u = vup.cross(w).normalized();
+        let v = w.cross(u);
+
+        // The viewport sits on the focus plane, so that's where rays from
+        // every point on the lens converge.
+        let origin = look_from;
+        let horizontal = focus_dist * viewport_width * u;
+        let vertical = focus_dist * viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
 
         Camera {
             origin,
             horizontal,
             vertical,
-            lower_left_corner
+            lower_left_corner,
+            u,
+            v,
+            w,
+            lens_radius: aperture / 2.0,
+            focus_dist,
+            projection,
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        Ray::new(self.origin,
-                 self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin)
+    pub fn get_ray(&self, rng: &mut ChaCha12Rng, u: f64, v: f64) -> Ray {
+        match self.projection {
+            Projection::Perspective => {
+                // Don't touch the rng for pinhole cameras, so they render
+                // exactly the same images they did before there was a lens.
+                let offset = if self.lens_radius > 0.0 {
+                    let rd = self.lens_radius * Vec3::random_in_unit_disk(rng);
+                    self.u * rd.x() + self.v * rd.y()
+                } else {
+                    Vec3::new(0.0, 0.0, 0.0)
+                };
+
+                Ray::new(self.origin + offset,
+                         self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset)
+            }
+            Projection::Orthographic => {
+                // The point on the viewport, pulled back from the focus plane
+                // onto the plane of the camera itself.
+                let origin = self.lower_left_corner + u * self.horizontal + v * self.vertical
+                    + self.focus_dist * self.w;
+                Ray::new(origin, -1.0 * self.w)
+            }
+            Projection::Equirectangular => {
+                // u sweeps all the way around (longitude), v goes from
+                // straight down to straight up (latitude).
+                let longitude = (u - 0.5) * 2.0 * PI;
+                let latitude = (v - 0.5) * PI;
+                let direction = latitude.cos() * (longitude.sin() * self.u - longitude.cos() * self.w)
+                    + latitude.sin() * self.v;
+                Ray::new(self.origin, direction)
+            }
+        }
     }
 
-}
\ No newline at end of file
+}
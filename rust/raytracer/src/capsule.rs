@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::roots::solve_quadratic;
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// Every point within radius of the line segment from start to end: a
+// cylinder with half a sphere on each end.
+#[derive(Serialize, Deserialize)]
+pub struct Capsule {
+    start: Point3,
+    end: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Capsule {
+    #[allow(unused)]
+    pub fn new(start: Point3, end: Point3, radius: f64, mat: Arc<dyn Material>) -> Capsule {
+        Capsule {
+            start,
+            end,
+            radius,
+            mat,
+        }
+    }
+
+    fn closest_point_on_segment(&self, p: Point3) -> Point3 {
+        let segment = self.end - self.start;
+        let length_squared = segment.dot(segment);
+        if length_squared == 0.0 {
+            return self.start;
+        }
+        let along = ((p - self.start).dot(segment) / length_squared).clamp(0.0, 1.0);
+        self.start + along * segment
+    }
+}
+
+#[typetag::serde]
+impl Hit for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let length = (self.end - self.start).length();
+        let axis = if length == 0.0 { Vec3::new(0.0, 0.0, 0.0) } else { (self.end - self.start) / length };
+        let d = r.direction();
+        let radius_squared = self.radius * self.radius;
+
+        // The hits on the middle part, between the ends, and the hits on the
+        // spheres at the ends that are past the ends. There can be more than
+        // two of these, so they're gathered up and the closest one is kept.
+        let mut candidates = Vec::with_capacity(6);
+
+        let oc = r.origin() - self.start;
+        let d_perpendicular = d - d.dot(axis) * axis;
+        let oc_perpendicular = oc - oc.dot(axis) * axis;
+        if let Some(roots) = solve_quadratic(
+            d_perpendicular.dot(d_perpendicular),
+            d_perpendicular.dot(oc_perpendicular),
+            oc_perpendicular.dot(oc_perpendicular) - radius_squared,
+        ) {
+            candidates.extend(roots.into_iter().filter(|&t| (0.0..=length).contains(&(oc + t * d).dot(axis))));
+        }
+
+        for (center, is_start) in [(self.start, true), (self.end, false)] {
+            let oc = r.origin() - center;
+            if let Some(roots) = solve_quadratic(d.dot(d), oc.dot(d), oc.dot(oc) - radius_squared) {
+                candidates.extend(roots.into_iter().filter(|&t| {
+                    let along = (r.at(t) - self.start).dot(axis);
+                    if is_start { along <= 0.0 } else { along >= length }
+                }));
+            }
+        }
+
+        let t = candidates.into_iter().filter(|&t| t >= t_min && t <= t_max).min_by(f64::total_cmp)?;
+        let p = r.at(t);
+        let outward_normal = (p - self.closest_point_on_segment(p)) / self.radius;
+        Some(HitRecord::with_normal_against_ray(p, t, r, outward_normal, self.mat.as_ref()))
+    }
+
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        (self.closest_point_on_segment(other.center()) - other.center()).length() < self.radius + other.radius()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.start - r, self.start + r).surrounding(&Aabb::new(self.end - r, self.end + r)))
+    }
+}
@@ -0,0 +1,72 @@
+// Color management.
+//
+// All of the rendering happens in linear light, with the same primaries (and
+// white point) as sRGB and Rec. 709. That goes for every color in a scene file
+// too: a material's albedo, its emission, the background, and so on. So
+// {"e": [0.5, 0.5, 0.5]} reflects half of the light that hits it, which
+// shows up as about 188, not 128, in an sRGB image. To match a color picked
+// from an sRGB image or color picker, convert it to linear with
+// TransferFunction::Srgb.decode first.
+//
+// Nothing gets encoded until it's written out to an 8-bit image, after tone
+// mapping. High dynamic range images stay linear.
+
+use clap_serde_derive::clap::{self, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use super::vec::Color;
+
+// How linear values get encoded for 8-bit images (what's technically called
+// an opto-electronic transfer function).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferFunction {
+    // The real, piecewise sRGB curve. What every image viewer expects.
+    #[default]
+    Srgb,
+    // No encoding at all. Looks dark in most viewers, but it's the right
+    // thing to feed into anything that expects linear data.
+    Linear,
+    // The square root, which is what this raytracer (and the tutorial) used
+    // to do. Only useful for matching old renders.
+    Gamma2,
+}
+
+impl TransferFunction {
+    pub fn encode(self, linear: Color) -> Color {
+        let encode_channel = |c: f64| {
+            let c = c.max(0.0);
+            match self {
+                TransferFunction::Srgb => {
+                    if c <= 0.0031308 {
+                        12.92 * c
+                    } else {
+                        1.055 * c.powf(1.0 / 2.4) - 0.055
+                    }
+                }
+                TransferFunction::Linear => c,
+                TransferFunction::Gamma2 => c.sqrt(),
+            }
+        };
+        Color::new(encode_channel(linear.x()), encode_channel(linear.y()), encode_channel(linear.z()))
+    }
+
+    #[allow(unused)]
+    pub fn decode(self, encoded: Color) -> Color {
+        let decode_channel = |c: f64| {
+            let c = c.max(0.0);
+            match self {
+                TransferFunction::Srgb => {
+                    if c <= 0.04045 {
+                        c / 12.92
+                    } else {
+                        ((c + 0.055) / 1.055).powf(2.4)
+                    }
+                }
+                TransferFunction::Linear => c,
+                TransferFunction::Gamma2 => c * c,
+            }
+        };
+        Color::new(decode_channel(encoded.x()), decode_channel(encoded.y()), decode_channel(encoded.z()))
+    }
+}
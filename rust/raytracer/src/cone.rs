@@ -0,0 +1,136 @@
+use std::io;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::cylinder::check_height;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::roots::solve_quadratic;
+use super::sphere::Sphere;
+use super::triangle::triangle_collides_with_sphere;
+use super::vec::{Point3, Vec3};
+
+// A solid cone, with a flat disk of the given radius at base, narrowing to a
+// point at apex.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "ConeSettings")]
+pub struct Cone {
+    base: Point3,
+    apex: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+// What a Cone looks like in a scene file, before it's been checked.
+#[derive(Deserialize)]
+struct ConeSettings {
+    base: Point3,
+    apex: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl TryFrom<ConeSettings> for Cone {
+    type Error = io::Error;
+
+    fn try_from(settings: ConeSettings) -> io::Result<Cone> {
+        check_height(settings.base, settings.apex, "A cone's base and apex")?;
+        Ok(Cone {
+            base: settings.base,
+            apex: settings.apex,
+            radius: settings.radius,
+            mat: settings.mat,
+        })
+    }
+}
+
+impl Cone {
+    #[allow(unused)]
+    pub fn new(base: Point3, apex: Point3, radius: f64, mat: Arc<dyn Material>) -> io::Result<Cone> {
+        Cone::try_from(ConeSettings {
+            base,
+            apex,
+            radius,
+            mat,
+        })
+    }
+}
+
+#[typetag::serde]
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let height = (self.base - self.apex).length();
+        // Pointing from the apex down to the base.
+        let axis = (self.base - self.apex) / height;
+        let slope = self.radius / height;
+        let d = r.direction();
+
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut closest_so_far = t_max;
+
+        // The sloped side. Measured from the apex, a point x is on the
+        // (double, infinite) cone when its distance from the axis is slope
+        // times how far down the axis it is, or:
+        // |x|^2 - (1 + slope^2) * (x . axis)^2 = 0
+        let from_apex = r.origin() - self.apex;
+        let k = 1.0 + slope * slope;
+        let (d_axis, from_apex_axis) = (d.dot(axis), from_apex.dot(axis));
+        if let Some(roots) = solve_quadratic(
+            d.dot(d) - k * d_axis * d_axis,
+            d.dot(from_apex) - k * d_axis * from_apex_axis,
+            from_apex.dot(from_apex) - k * from_apex_axis * from_apex_axis,
+        ) {
+            for t in roots {
+                let x = from_apex + t * d;
+                let down = x.dot(axis);
+                if t >= t_min && t <= closest_so_far && (0.0..=height).contains(&down) {
+                    // The gradient of the equation above.
+                    let outward_normal = x - k * down * axis;
+                    if outward_normal.near_zero() {
+                        // Right at the tip.
+                        continue;
+                    }
+                    closest = Some((t, outward_normal.normalized()));
+                    closest_so_far = t;
+                }
+            }
+        }
+
+        // The flat base.
+        if d_axis != 0.0 {
+            let t = (self.base - r.origin()).dot(axis) / d_axis;
+            if t >= t_min && t <= closest_so_far && (r.at(t) - self.base).length() <= self.radius {
+                closest = Some((t, axis));
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        Some(HitRecord::with_normal_against_ray(r.at(t), t, r, outward_normal, self.mat.as_ref()))
+    }
+
+    // Same idea as Cylinder::collides_with_sphere, with the cone's cross
+    // section being a single triangle.
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        let axis = (self.base - self.apex).normalized();
+        let from_base = other.center() - self.base;
+        let outwards = from_base - from_base.dot(axis) * axis;
+        let outwards = if outwards.near_zero() { axis.orthonormal_basis().0 } else { outwards.normalized() };
+
+        triangle_collides_with_sphere([self.base, self.base + self.radius * outwards, self.apex], other)
+    }
+
+    // See Cylinder::bounding_box, but there's only the one disk, plus the
+    // apex.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = (self.base - self.apex).normalized();
+        let mut extent = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            extent[i] = self.radius * (1.0 - axis[i] * axis[i]).max(0.0).sqrt();
+        }
+        let bbox = Aabb::new(self.base - extent, self.base + extent).surrounding(&Aabb::new(self.apex, self.apex));
+        Some(bbox.padded(1e-4))
+    }
+}
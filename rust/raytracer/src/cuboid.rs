@@ -0,0 +1,139 @@
+use std::io;
+use std::sync::Arc;
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::quad::Quad;
+use super::ray::Ray;
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// What a Cuboid looks like in a scene file: two opposite corners, in either
+// order.
+#[derive(Serialize, Deserialize)]
+struct CuboidSettings {
+    corners: [Point3; 2],
+    mat: Arc<dyn Material>,
+}
+
+// An axis-aligned box, made of six Quads facing outwards. It's called "Box"
+// in scene files, but that name's taken in Rust.
+#[derive(Deserialize)]
+#[serde(try_from = "CuboidSettings")]
+pub struct Cuboid {
+    settings: CuboidSettings,
+    sides: [Quad; 6],
+    bbox: Aabb,
+}
+
+// A box that's flat in any direction isn't really a box, and one that's flat
+// in more than one has no area to pick light samples from.
+impl TryFrom<CuboidSettings> for Cuboid {
+    type Error = io::Error;
+
+    fn try_from(settings: CuboidSettings) -> io::Result<Cuboid> {
+        let [a, b] = settings.corners;
+        let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        if (0..3).any(|axis| min[axis] == max[axis]) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "A box's corners have to differ along every axis."));
+        }
+
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+        // Each pair of edges is in the order that makes its cross product
+        // point out of the box.
+        let side = |corner: Point3, u: Vec3, v: Vec3| Quad::new(corner, u, v, settings.mat.clone());
+        let sides = [
+            side(max, -1.0 * dy, -1.0 * dz), // +x
+            side(min, dz, dy),               // -x
+            side(max, -1.0 * dz, -1.0 * dx), // +y
+            side(min, dx, dz),               // -y
+            side(max, -1.0 * dx, -1.0 * dy), // +z
+            side(min, dy, dx),               // -z
+        ];
+
+        Ok(Cuboid {
+            settings,
+            sides,
+            bbox: Aabb::new(min, max).padded(1e-4),
+        })
+    }
+}
+
+impl Serialize for Cuboid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.settings.serialize(serializer)
+    }
+}
+
+impl Cuboid {
+    #[allow(unused)]
+    pub fn new(corners: [Point3; 2], mat: Arc<dyn Material>) -> io::Result<Cuboid> {
+        Cuboid::try_from(CuboidSettings {
+            corners,
+            mat,
+        })
+    }
+}
+
+#[typetag::serde(name = "Box")]
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        for side in &self.sides {
+            if let Some(rec) = side.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
+    // The box is solid, so a sphere entirely inside of it collides too, even
+    // though it doesn't touch any of the sides.
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        let center = other.center();
+        let (min, max) = (self.bbox.min(), self.bbox.max());
+        let inside = (0..3).all(|axis| min[axis] <= center[axis] && center[axis] <= max[axis]);
+        inside || self.sides.iter().any(|side| side.collides_with_sphere(other))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    fn is_light(&self) -> bool {
+        self.settings.mat.is_emissive()
+    }
+
+    // Picks a side with odds in proportion to its area, and then a point on
+    // that side (see Quad::sample_towards), which comes out to picking
+    // uniformly over the whole surface.
+    fn sample_towards(&self, rng: &mut ChaCha12Rng, origin: Point3) -> Option<Vec3> {
+        let total_area = self.sides.iter().map(Quad::area).sum::<f64>();
+        let mut pick = rng.gen::<f64>() * total_area;
+        for side in &self.sides[..5] {
+            if pick < side.area() {
+                return side.sample_towards(rng, origin);
+            }
+            pick -= side.area();
+        }
+        self.sides[5].sample_towards(rng, origin)
+    }
+
+    // A direction can come from any of the sides it passes through (a point
+    // on the far side of the box is just as likely to get picked as one on
+    // the near side), so this adds up the odds for each of them.
+    fn pdf_towards(&self, origin: Point3, direction: Vec3) -> f64 {
+        let total_area = self.sides.iter().map(Quad::area).sum::<f64>();
+        self.sides.iter().map(|side| side.area() / total_area * side.pdf_towards(origin, direction)).sum()
+    }
+}
@@ -0,0 +1,145 @@
+use std::io;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::roots::solve_quadratic;
+use super::sphere::Sphere;
+use super::triangle::triangle_collides_with_sphere;
+use super::vec::{Point3, Vec3};
+
+// A solid cylinder, capped with flat ends. base and top are the centers of
+// the ends, so the cylinder can point any which way.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "CylinderSettings")]
+pub struct Cylinder {
+    base: Point3,
+    top: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+// What a Cylinder looks like in a scene file, before it's been checked.
+#[derive(Deserialize)]
+struct CylinderSettings {
+    base: Point3,
+    top: Point3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+// Checks that a shape running from one end to the other (like a cylinder or
+// a cone) has some height. With none, there'd be no axis to measure anything
+// along, and the NaNs from trying would spread into the bounding boxes of
+// everything around it in the Bvh. ends names them, for the error message.
+pub fn check_height(start: Point3, end: Point3, ends: &str) -> io::Result<()> {
+    if (end - start).length() == 0.0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} can't be the same point.", ends)));
+    }
+    Ok(())
+}
+
+impl TryFrom<CylinderSettings> for Cylinder {
+    type Error = io::Error;
+
+    fn try_from(settings: CylinderSettings) -> io::Result<Cylinder> {
+        check_height(settings.base, settings.top, "A cylinder's base and top")?;
+        Ok(Cylinder {
+            base: settings.base,
+            top: settings.top,
+            radius: settings.radius,
+            mat: settings.mat,
+        })
+    }
+}
+
+impl Cylinder {
+    #[allow(unused)]
+    pub fn new(base: Point3, top: Point3, radius: f64, mat: Arc<dyn Material>) -> io::Result<Cylinder> {
+        Cylinder::try_from(CylinderSettings {
+            base,
+            top,
+            radius,
+            mat,
+        })
+    }
+}
+
+#[typetag::serde]
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let height = (self.top - self.base).length();
+        let axis = (self.top - self.base) / height;
+        let oc = r.origin() - self.base;
+        let d = r.direction();
+
+        // The closest hit so far, as t and its outward normal.
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut closest_so_far = t_max;
+
+        // The curved side: only the parts of the ray and the origin that are
+        // perpendicular to the axis matter, which makes this the same as
+        // hitting a circle in 2D.
+        let d_perpendicular = d - d.dot(axis) * axis;
+        let oc_perpendicular = oc - oc.dot(axis) * axis;
+        if let Some(roots) = solve_quadratic(
+            d_perpendicular.dot(d_perpendicular),
+            d_perpendicular.dot(oc_perpendicular),
+            oc_perpendicular.dot(oc_perpendicular) - self.radius * self.radius,
+        ) {
+            for t in roots {
+                let height_at_t = (oc + t * d).dot(axis);
+                if t >= t_min && t <= closest_so_far && (0.0..=height).contains(&height_at_t) {
+                    let outward_normal = (oc + t * d - height_at_t * axis) / self.radius;
+                    closest = Some((t, outward_normal));
+                    closest_so_far = t;
+                }
+            }
+        }
+
+        // The flat ends.
+        let denominator = d.dot(axis);
+        if denominator != 0.0 {
+            for (center, outward_normal) in [(self.base, -1.0 * axis), (self.top, axis)] {
+                let t = (center - r.origin()).dot(axis) / denominator;
+                if t >= t_min && t <= closest_so_far && (r.at(t) - center).length() <= self.radius {
+                    closest = Some((t, outward_normal));
+                    closest_so_far = t;
+                }
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        Some(HitRecord::with_normal_against_ray(r.at(t), t, r, outward_normal, self.mat.as_ref()))
+    }
+
+    // The cylinder is the same all the way around, so the closest point on
+    // it to the sphere's center is in the rectangle running from the axis
+    // out towards the center, which is just two triangles.
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        let axis = (self.top - self.base).normalized();
+        let from_base = other.center() - self.base;
+        let outwards = from_base - from_base.dot(axis) * axis;
+        let outwards = if outwards.near_zero() { axis.orthonormal_basis().0 } else { outwards.normalized() };
+
+        let edge = self.radius * outwards;
+        triangle_collides_with_sphere([self.base, self.base + edge, self.top + edge], other)
+            || triangle_collides_with_sphere([self.base, self.top + edge, self.top], other)
+    }
+
+    // Each end is a disk, whose extent along each axis is radius times the
+    // sine of the angle between that axis and the cylinder's.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = (self.top - self.base).normalized();
+        let mut extent = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            extent[i] = self.radius * (1.0 - axis[i] * axis[i]).max(0.0).sqrt();
+        }
+        let bbox = Aabb::new(self.base - extent, self.base + extent).surrounding(&Aabb::new(self.top - extent, self.top + extent));
+        Some(bbox.padded(1e-4))
+    }
+}
@@ -0,0 +1,114 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// A flat circle, facing the way normal points.
+#[derive(Serialize, Deserialize)]
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Disk {
+    #[allow(unused)]
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Material>) -> Disk {
+        Disk {
+            center,
+            normal,
+            radius,
+            mat,
+        }
+    }
+
+    fn intersect(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let denominator = self.normal.dot(r.direction());
+        // See Quad::intersect.
+        if denominator.abs() <= 1e-12 * self.normal.length() * r.direction().length() {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - r.origin()) / denominator;
+        if t < t_min || t > t_max || (r.at(t) - self.center).length() > self.radius {
+            return None;
+        }
+
+        Some(t)
+    }
+
+    // The closest point on the disk to p: p flattened onto the disk's plane,
+    // then pulled in to the edge if it's outside of it.
+    fn closest_point(&self, p: Point3) -> Point3 {
+        let n = self.normal.normalized();
+        let from_center = p - self.center;
+        let in_plane = from_center - from_center.dot(n) * n;
+        let distance = in_plane.length();
+        if distance > self.radius {
+            self.center + self.radius / distance * in_plane
+        } else {
+            self.center + in_plane
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let t = self.intersect(r, t_min, t_max)?;
+        Some(HitRecord::with_normal_against_ray(r.at(t), t, r, self.normal.normalized(), self.mat.as_ref()))
+    }
+
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        (self.closest_point(other.center()) - other.center()).length() < other.radius()
+    }
+
+    // How far the disk reaches along each axis is radius times the sine of
+    // the angle between that axis and the normal.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let n = self.normal.normalized();
+        let mut extent = Vec3::new(0.0, 0.0, 0.0);
+        for axis in 0..3 {
+            extent[axis] = self.radius * (1.0 - n[axis] * n[axis]).max(0.0).sqrt();
+        }
+        Some(Aabb::new(self.center - extent, self.center + extent).padded(1e-4))
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    // Picks a point uniformly over the disk's area.
+    fn sample_towards(&self, rng: &mut ChaCha12Rng, origin: Point3) -> Option<Vec3> {
+        let (u, v) = self.normal.normalized().orthonormal_basis();
+        let distance = self.radius * rng.gen::<f64>().sqrt();
+        let angle = 2.0 * PI * rng.gen::<f64>();
+        let point = self.center + distance * angle.cos() * u + distance * angle.sin() * v;
+        let direction = point - origin;
+        if direction.near_zero() {
+            return None;
+        }
+        Some(direction.normalized())
+    }
+
+    // See Quad::pdf_towards.
+    fn pdf_towards(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(t) = self.intersect(&Ray::new(origin, direction), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let area = PI * self.radius * self.radius;
+        let distance_squared = (t * direction.length()).powi(2);
+        let cosine = direction.normalized().dot(self.normal.normalized()).abs();
+        distance_squared / (cosine * area)
+    }
+}
@@ -1,25 +1,29 @@
-use std::rc::Rc;
+use rand_chacha::ChaCha12Rng;
 
 use super::sphere::Sphere;
+use super::aabb::Aabb;
 
 use super::material::Material;
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
 
-pub struct HitRecord {
+// Borrows the material from whatever was hit, rather than holding an Arc to
+// it, so that threads hitting the same object aren't all fighting over its
+// reference count.
+pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Rc<dyn Material>,
+    pub mat: &'a dyn Material,
     pub t: f64,
     pub front_face: bool
 }
 
-impl HitRecord {
+impl<'a> HitRecord<'a> {
     // Note: This is dodgy, I'm changing their code, but I really don't like
     // making the HitRecord with dummy values only to overwrite them.
     // And this way, I can have correctly initialized HitRecords without
     // necessarily making them mutable.
-    pub fn with_normal_against_ray(p: Point3, t: f64, r: &Ray, outward_normal: Vec3, mat: Rc<dyn Material>) -> HitRecord {
+    pub fn with_normal_against_ray(p: Point3, t: f64, r: &Ray, outward_normal: Vec3, mat: &'a dyn Material) -> HitRecord<'a> {
         let front_face = r.direction().dot(outward_normal) < 0.0;
         HitRecord {
             p,
@@ -33,9 +37,29 @@ impl HitRecord {
 
 
 #[typetag::serde(tag = "type")]
-pub trait Hit {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+pub trait Hit: Send + Sync {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
     fn collides_with_sphere(&self, other: &Sphere) -> bool;
+    // None for objects that go on forever, which can't be put in a Bvh.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Objects that give off light and know how to sample directions towards
+    // themselves are used as area lights, so the renderer can aim shadow rays
+    // at them instead of waiting for a bounce to find them by chance. The
+    // defaults are for everything else.
+    fn is_light(&self) -> bool {
+        false
+    }
+    // A direction from origin towards some point on this object. None if
+    // there isn't a good one, like when origin is inside of it.
+    fn sample_towards(&self, _rng: &mut ChaCha12Rng, _origin: Point3) -> Option<Vec3> {
+        None
+    }
+    // The probability density (per steradian) of sample_towards picking
+    // direction, which doesn't have to be a unit vector.
+    fn pdf_towards(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
 }
 
 pub type World = Vec<Box<dyn Hit>>;
@@ -45,7 +69,7 @@ pub type World = Vec<Box<dyn Hit>>;
 
 #[typetag::serde]
 impl Hit for World {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut tmp_rec = None;
 
         let mut closest_so_far = t_max;
@@ -64,4 +88,9 @@ impl Hit for World {
     fn collides_with_sphere(&self, _other: &Sphere) -> bool {
         unimplemented!("Can't collide world with sphere. How did we get here.")
     }
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |bbox, object| Some(bbox.surrounding(&object.bounding_box()?)))
+    }
 }
\ No newline at end of file
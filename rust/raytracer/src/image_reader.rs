@@ -0,0 +1,252 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use exr::prelude::read_first_rgba_layer_from_file;
+
+use super::vec::Color;
+
+// A high dynamic range image, in linear light.
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    // Row by row, from the top left.
+    pub pixels: Vec<Color>,
+}
+
+impl HdrImage {
+    // Reads a Radiance (.hdr) or OpenEXR (.exr) image, going by the
+    // extension.
+    pub fn load(path: &Path) -> io::Result<HdrImage> {
+        let with_path = |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("hdr") => read_radiance(BufReader::new(File::open(path).map_err(with_path)?)).map_err(with_path),
+            Some("exr") => read_exr(path).map_err(with_path),
+            _ => Err(with_path(invalid_data("Only .hdr and .exr images are supported."))),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_exr(path: &Path) -> io::Result<HdrImage> {
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| HdrImage {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![Color::default(); resolution.width() * resolution.height()],
+        },
+        |image: &mut HdrImage, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+            image.pixels[position.y() * image.width + position.x()] = Color::new(r as f64, g as f64, b as f64);
+        },
+    ).map_err(|err| match err {
+        exr::error::Error::Io(err) => err,
+        err => invalid_data(err.to_string()),
+    })?;
+
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+// Way bigger than any real environment map, but small enough that a scanline
+// always fits in memory.
+const MAX_RADIANCE_SIZE: usize = 1 << 16;
+
+// Radiance's RGBE format: a text header, a resolution line, and then every
+// pixel as an 8-bit mantissa for each channel plus a shared 8-bit exponent,
+// usually run-length encoded one scanline at a time.
+// See https://www.graphics.cornell.edu/~bjw/rgbe.html for the details.
+fn read_radiance(mut reader: impl BufRead) -> io::Result<HdrImage> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("Not a Radiance HDR file (it doesn't start with \"#?\")."));
+    }
+
+    // The rest of the header ends with a blank line.
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("The header never ends."));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data(format!("Unsupported pixel format {}.", format)));
+            }
+        }
+    }
+
+    // Only the standard orientation, with rows from the top down and columns
+    // from left to right.
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (height, width) = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["-Y", height, "+X", width] => (
+            height.parse::<usize>().map_err(|_| invalid_data("Bad image height."))?,
+            width.parse::<usize>().map_err(|_| invalid_data("Bad image width."))?,
+        ),
+        _ => return Err(invalid_data(format!("Unsupported resolution line {:?}.", line.trim()))),
+    };
+    if width == 0 || height == 0 {
+        return Err(invalid_data("The image has no pixels."));
+    }
+    if width > MAX_RADIANCE_SIZE || height > MAX_RADIANCE_SIZE {
+        return Err(invalid_data(format!("The image is bigger than {0}x{0}.", MAX_RADIANCE_SIZE)));
+    }
+    let num_pixels = width.checked_mul(height).ok_or_else(|| invalid_data("The image has too many pixels."))?;
+
+    // Only grown as scanlines actually get read, so a header claiming a huge
+    // image doesn't allocate it all up front.
+    let mut pixels = Vec::with_capacity(num_pixels.min(MAX_RADIANCE_SIZE));
+    let mut scanline = vec![[0u8; 4]; width];
+    for _ in 0..height {
+        read_radiance_scanline(&mut reader, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+    }
+
+    Ok(HdrImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn read_radiance_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+
+    // Scanlines in the newer run-length encoding start with 2, 2, and then
+    // the width. Anything else is the older flat format.
+    if !(8..0x8000).contains(&width) || first[0] != 2 || first[1] != 2 || first[2] & 0x80 != 0 {
+        scanline[0] = first;
+        return read_flat_scanline(reader, scanline);
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != width {
+        return Err(invalid_data("A scanline's width doesn't match the image's."));
+    }
+
+    // Each channel is encoded separately, as a series of runs (a count over
+    // 128, and a byte to repeat) and literal spans (a count, and that many
+    // bytes).
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let count = count[0] as usize;
+            if count > 128 {
+                let count = count - 128;
+                if x + count > width {
+                    return Err(invalid_data("A run goes past the end of its scanline."));
+                }
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("A span goes past the end of its scanline."));
+                }
+                let mut values = vec![0u8; count];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The first pixel has already been read. Handles the original run-length
+// encoding too, where a pixel of 1, 1, 1 means to repeat the last pixel.
+fn read_flat_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let mut x = 1;
+    let mut shift = 0;
+    while x < scanline.len() {
+        let mut pixel = [0u8; 4];
+        reader.read_exact(&mut pixel)?;
+        if pixel[0] == 1 && pixel[1] == 1 && pixel[2] == 1 {
+            // Each run in a row is another 8 bits of the count, and a count
+            // of 0 would never get anywhere.
+            if pixel[3] == 0 {
+                return Err(invalid_data("A run has no pixels in it."));
+            }
+            if shift >= usize::BITS {
+                return Err(invalid_data("A run goes past the end of its scanline."));
+            }
+            let count = (pixel[3] as usize) << shift;
+            if x + count > scanline.len() {
+                return Err(invalid_data("A run goes past the end of its scanline."));
+            }
+            let previous = scanline[x - 1];
+            scanline[x..x + count].fill(previous);
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = pixel;
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(())
+}
+
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(e as i32 - (128 + 8));
+    Color::new((r as f64 + 0.5) * scale, (g as f64 + 0.5) * scale, (b as f64 + 0.5) * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn radiance(resolution: &str, body: &[u8]) -> io::Result<HdrImage> {
+        let mut file = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes();
+        file.extend_from_slice(body);
+        read_radiance(file.as_slice())
+    }
+
+    #[test]
+    fn reads_flat_pixels() {
+        let image = radiance("-Y 1 +X 3", &[128, 0, 0, 129, 1, 1, 1, 2]).unwrap();
+        assert_eq!((image.width, image.height), (3, 1));
+        assert!(image.pixels.iter().all(|pixel| pixel.x() > 1.0 && pixel.y() < 0.01));
+    }
+
+    #[test]
+    fn empty_or_huge_images_are_errors() {
+        assert!(radiance("-Y 1 +X 0", &[]).is_err());
+        assert!(radiance("-Y 0 +X 1", &[]).is_err());
+        assert!(radiance("-Y 99999999999 +X 99999999999", &[]).is_err());
+        assert!(radiance(&format!("-Y {} +X {}", usize::MAX, usize::MAX), &[]).is_err());
+    }
+
+    #[test]
+    fn empty_runs_are_errors() {
+        let mut body = vec![128, 0, 0, 129];
+        for _ in 0..16 {
+            body.extend_from_slice(&[1, 1, 1, 0]);
+        }
+        assert!(radiance("-Y 1 +X 3", &body).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{self, Cursor, Write};
+use std::path::Path;
+
+use clap_serde_derive::clap::{self, ValueEnum};
+use exr::prelude::{f16, Image, SpecificChannels, Vec2, WritableImage};
+use serde::{Deserialize, Serialize};
+
+use super::color::TransferFunction;
+use super::tonemap::ToneMapping;
+use super::vec::Color;
+
+// The accumulated (summed, not yet averaged) samples for every pixel. Rows go
+// from the top of the image down, the way they're written out.
+pub type PixelGrid = Vec<Vec<Color>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageFormat {
+    // Plain (P3) PPM, with every channel written out in ASCII.
+    Ppm,
+    // Raw (P6) PPM, with every channel written out as a byte. Much smaller
+    // than plain PPM, and still just as easy to read.
+    PpmBinary,
+    Png,
+    // OpenEXR with half (16-bit) floats. Like the other high dynamic range
+    // formats, this gets the raw linear radiance, without any gamma correction
+    // or clamping, so it can be re-exposed later without re-rendering.
+    Exr,
+    // OpenEXR with full (32-bit) floats.
+    ExrFloat,
+    // Portable float map. Little-endian 32-bit floats, and about as simple as
+    // a high dynamic range format can get.
+    Pfm,
+}
+
+impl ImageFormat {
+    // .ppm files get plain PPM, since that's what they've always been, and
+    // .exr files get half floats, since that's plenty for images.
+    pub fn from_extension(path: &Path) -> Option<ImageFormat> {
+        match path.extension()?.to_str()? {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "exr" => Some(ImageFormat::Exr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+// tone_mapping and transfer_function only apply to the 8-bit formats. The
+// high dynamic range ones always get the raw, linear radiance.
+pub fn write_image(
+    output: &mut impl Write,
+    format: ImageFormat,
+    image: &PixelGrid,
+    samples_per_pixel: u64,
+    tone_mapping: &ToneMapping,
+    transfer_function: TransferFunction,
+) -> io::Result<()> {
+    let to_rgb8 = |pixel_color: Color| {
+        transfer_function.encode(tone_mapping.apply(average(pixel_color, samples_per_pixel))).to_rgb8()
+    };
+
+    let height = image.len();
+    let width = image.first().map_or(0, |scanline| scanline.len());
+
+    match format {
+        ImageFormat::Ppm => {
+            writeln!(output, "P3")?;
+            writeln!(output, "{} {}", width, height)?;
+            writeln!(output, "255")?;
+
+            for scanline in image {
+                for &pixel_color in scanline {
+                    let [r, g, b] = to_rgb8(pixel_color);
+                    write!(output, "{} {} {} ", r, g, b)?;
+                }
+                writeln!(output)?;
+            }
+        }
+        ImageFormat::PpmBinary => {
+            writeln!(output, "P6")?;
+            writeln!(output, "{} {}", width, height)?;
+            writeln!(output, "255")?;
+
+            output.write_all(&rgb8_bytes(image, to_rgb8))?;
+        }
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut *output, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            // Tell viewers how to decode it.
+            match transfer_function {
+                TransferFunction::Srgb => encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual),
+                TransferFunction::Linear => encoder.set_source_gamma(png::ScaledFloat::new(1.0)),
+                TransferFunction::Gamma2 => encoder.set_source_gamma(png::ScaledFloat::new(0.5)),
+            }
+
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(&rgb8_bytes(image, to_rgb8))?;
+            writer.finish()?;
+        }
+        ImageFormat::Exr | ImageFormat::ExrFloat => {
+            // OpenEXR files have to be seekable while they're being written,
+            // and standard output isn't, so write it to memory first.
+            let mut buffer = Cursor::new(Vec::new());
+            let radiance = |position: Vec2<usize>| {
+                let Vec2(x, y) = position;
+                average(image[y][x], samples_per_pixel)
+            };
+
+            let result = if format == ImageFormat::Exr {
+                Image::from_channels((width, height), SpecificChannels::rgb(|position| {
+                    let pixel_color = radiance(position);
+                    (f16::from_f64(pixel_color.x()), f16::from_f64(pixel_color.y()), f16::from_f64(pixel_color.z()))
+                })).write().to_buffered(&mut buffer)
+            } else {
+                Image::from_channels((width, height), SpecificChannels::rgb(|position| {
+                    let pixel_color = radiance(position);
+                    (pixel_color.x() as f32, pixel_color.y() as f32, pixel_color.z() as f32)
+                })).write().to_buffered(&mut buffer)
+            };
+            result.map_err(io::Error::other)?;
+
+            output.write_all(buffer.get_ref())?;
+        }
+        ImageFormat::Pfm => {
+            writeln!(output, "PF")?;
+            writeln!(output, "{} {}", width, height)?;
+            // A negative scale means little-endian.
+            writeln!(output, "-1.0")?;
+
+            // Unlike every other format here, PFM goes from the bottom row up.
+            for scanline in image.iter().rev() {
+                for &pixel_color in scanline {
+                    let pixel_color = average(pixel_color, samples_per_pixel);
+                    for channel in 0..3 {
+                        output.write_all(&(pixel_color[channel] as f32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+    }
+
+    output.flush()
+}
+
+// The linear radiance the samples add up to.
+fn average(pixel_color: Color, samples_per_pixel: u64) -> Color {
+    pixel_color / samples_per_pixel as f64
+}
+
+// Every channel of every pixel, from the top left, one byte each.
+fn rgb8_bytes(image: &PixelGrid, to_rgb8: impl Fn(Color) -> [u8; 3]) -> Vec<u8> {
+    image
+        .iter()
+        .flatten()
+        .flat_map(|&pixel_color| to_rgb8(pixel_color))
+        .collect()
+}
@@ -0,0 +1,384 @@
+use clap_serde_derive::clap::{self, ValueEnum};
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::hit::HitRecord;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec::{Color, Point3, Vec3};
+
+// Turns camera rays into colors. The path tracer is what makes the actual
+// images; the rest are for seeing what's going on in a scene, like whether
+// its normals point the right way, or where its objects really are.
+pub trait Integrator: Send + Sync {
+    fn ray_color(&self, r: Ray, scene: &Scene, rng: &mut ChaCha12Rng) -> Color;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegratorKind {
+    // The real thing, with light bouncing all over (PathTracer).
+    #[default]
+    Path,
+    // Debugging views of the first thing each ray hits. See the integrators
+    // with the same names for what their colors mean.
+    Normals,
+    Depth,
+    Albedo,
+    AmbientOcclusion,
+    // Direct light only (Whitted).
+    Whitted,
+}
+
+// Where a ray was scattered from, when the lights were also sampled there (see
+// sample_lights), along with the probability density of scattering in the
+// ray's direction.
+#[derive(Clone, Copy)]
+struct LightSampledBounce {
+    origin: Point3,
+    scatter_pdf: f64,
+}
+
+// Follows a ray as it bounces around the scene, adding up the light it
+// picks up along the way.
+//
+// At every bounce that can be, the lights are sampled directly too, and the
+// light from emissive objects is counted both ways: once from the shadow ray,
+// and once if the scattered ray hits the light. Each gets weighted by how
+// good its strategy was at finding that light (multiple importance sampling),
+// so the weights add up to 1.0 and nothing gets counted twice.
+//
+// After min_depth bounces, paths are ended at random with Russian roulette,
+// more often the less light they could still carry back, and the ones that
+// survive are boosted to make up for the ones that didn't. That keeps the
+// average right, without wasting time on paths that barely matter. max_depth
+// is just a safety cap, for paths that keep bouncing around inside of
+// something that never absorbs anything.
+pub struct PathTracer {
+    min_depth: u64,
+    max_depth: u64,
+}
+
+impl PathTracer {
+    pub fn new(min_depth: u64, max_depth: u64) -> PathTracer {
+        PathTracer {
+            min_depth,
+            max_depth,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn ray_color(&self, mut r: Ray, scene: &Scene, rng: &mut ChaCha12Rng) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let mut color = black;
+        // How much of the light found from here on makes it back to the camera.
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        // Where r came from, if the lights were sampled there.
+        let mut previous: Option<LightSampledBounce> = None;
+
+        for depth in 1..=self.max_depth {
+            let Some((index, rec)) = scene.objects.hit_with_index(&r, 0.001, f64::INFINITY) else {
+                color += throughput * scene.background.color(&r);
+                break;
+            };
+
+            // Light given off counts whether or not the ray goes on from here.
+            let weight = match previous {
+                Some(previous) if scene.objects.object(index).is_light() => {
+                    power_heuristic(previous.scatter_pdf, light_pdf(scene, index, previous.origin, r.direction()))
+                }
+                _ => 1.0,
+            };
+            color += throughput * weight * rec.mat.emit(rng, &r, &rec);
+
+            // On the last bounce, the scattered ray doesn't get to pick up any
+            // light, so neither do shadow rays. Otherwise, the shadow ray
+            // counts even if scattering fails (like a fuzzy metal's ray
+            // going below the surface), since the weights assume it's always
+            // taken.
+            let direct = if depth < self.max_depth { sample_lights(&r, &rec, scene, true, rng) } else { None };
+            color += throughput * direct.unwrap_or(black);
+
+            let Some((attenuation, scattered)) = rec.mat.scatter(rng, &r, &rec) else {
+                break;
+            };
+            previous = direct.map(|_| LightSampledBounce {
+                origin: rec.p,
+                scatter_pdf: rec.mat.pdf(&r, &rec, scattered.direction()),
+            });
+
+            throughput = throughput * attenuation;
+            if depth >= self.min_depth && depth < self.max_depth {
+                let survival_probability = throughput.x().max(throughput.y()).max(throughput.z()).min(1.0);
+                if rng.gen::<f64>() >= survival_probability {
+                    break;
+                }
+                throughput /= survival_probability;
+            }
+
+            r = scattered;
+        }
+
+        color
+    }
+}
+
+// Only direct light, the way Whitted's raytracer worked: rays bounce off of
+// mirrors and through glass, but everything else just gets lit by the
+// lights, with no light bouncing between objects. The background only shows
+// up where rays miss everything, since it isn't a light that can be sampled.
+pub struct Whitted {
+    max_depth: u64,
+}
+
+impl Whitted {
+    pub fn new(max_depth: u64) -> Whitted {
+        Whitted {
+            max_depth,
+        }
+    }
+}
+
+impl Integrator for Whitted {
+    fn ray_color(&self, mut r: Ray, scene: &Scene, rng: &mut ChaCha12Rng) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let mut color = black;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+        for _ in 0..self.max_depth {
+            let Some(rec) = scene.objects.hit(&r, 0.001, f64::INFINITY) else {
+                color += throughput * scene.background.color(&r);
+                break;
+            };
+            color += throughput * rec.mat.emit(rng, &r, &rec);
+
+            // Anything that isn't a perfect mirror or glass (see
+            // Scatter::eval) stops here.
+            if rec.mat.eval(&r, &rec, rec.normal).is_some() {
+                color += throughput * sample_lights(&r, &rec, scene, false, rng).unwrap_or(black);
+                break;
+            }
+
+            let Some((attenuation, scattered)) = rec.mat.scatter(rng, &r, &rec) else {
+                break;
+            };
+            throughput = throughput * attenuation;
+            r = scattered;
+        }
+
+        color
+    }
+}
+
+// The normal of whatever each ray hits first, facing outwards, with each
+// component mapped from -1.0..1.0 to 0.0..1.0. So surfaces facing +x are red,
+// +y green, and +z blue. Black where rays miss.
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn ray_color(&self, r: Ray, scene: &Scene, _rng: &mut ChaCha12Rng) -> Color {
+        match scene.objects.hit(&r, 0.001, f64::INFINITY) {
+            Some(rec) => {
+                let outward_normal = if rec.front_face { rec.normal } else { -1.0 * rec.normal };
+                0.5 * (outward_normal.normalized() + Color::new(1.0, 1.0, 1.0))
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// How far each ray goes before hitting anything, in scene units, as a shade
+// of gray. That's usually way past 1.0, so this is best written to a high
+// dynamic range format, or brought down with --exposure. Black where rays
+// miss.
+pub struct Depth;
+
+impl Integrator for Depth {
+    fn ray_color(&self, r: Ray, scene: &Scene, _rng: &mut ChaCha12Rng) -> Color {
+        match scene.objects.hit(&r, 0.001, f64::INFINITY) {
+            Some(rec) => {
+                let distance = rec.t * r.direction().length();
+                Color::new(distance, distance, distance)
+            }
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// The albedo (see Scatter::albedo) of whatever each ray hits first, with no
+// lighting at all. Black where rays miss.
+pub struct Albedo;
+
+impl Integrator for Albedo {
+    fn ray_color(&self, r: Ray, scene: &Scene, _rng: &mut ChaCha12Rng) -> Color {
+        match scene.objects.hit(&r, 0.001, f64::INFINITY) {
+            Some(rec) => rec.mat.albedo(),
+            None => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// White where a random (cosine-weighted) ray from the first hit gets further
+// than distance without hitting anything, and black where it doesn't, so
+// with enough samples, creases and corners come out darker. Rays that miss
+// everything are white.
+pub struct AmbientOcclusion {
+    distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f64) -> AmbientOcclusion {
+        AmbientOcclusion {
+            distance,
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(&self, r: Ray, scene: &Scene, rng: &mut ChaCha12Rng) -> Color {
+        let Some(rec) = scene.objects.hit(&r, 0.001, f64::INFINITY) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+
+        let mut direction = rec.normal + Vec3::random_in_unit_sphere(rng).normalized();
+        if direction.near_zero() {
+            direction = rec.normal;
+        }
+        if scene.objects.hit(&Ray::new(rec.p, direction.normalized()), 0.001, self.distance).is_some() {
+            Color::new(0.0, 0.0, 0.0)
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+// Veach's power heuristic (with a power of 2), for weighing a sample from a
+// strategy with probability density pdf against one with other_pdf.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+    if pdf + other_pdf == 0.0 {
+        0.0
+    } else {
+        pdf / (pdf + other_pdf)
+    }
+}
+
+// The probability density of sample_lights picking direction from origin
+// towards the object at index (which must be a light), including the odds of
+// picking that light in the first place.
+fn light_pdf(scene: &Scene, index: usize, origin: Point3, direction: Vec3) -> f64 {
+    let num_lights = scene.lights.len() + scene.objects.lights().len();
+    scene.objects.object(index).pdf_towards(origin, direction) / num_lights as f64
+}
+
+// Next event estimation: picks one of the scene's lights (including emissive
+// objects) at random, and sends a shadow ray towards it to see how much of
+// its light reaches rec.p and leaves along r. None if there are no lights, or
+// the material can't be lit this way (see Scatter::eval).
+//
+// If the caller will also find light by scattering (with_mis), light from
+// emissive objects gets weighted against that. Otherwise, it counts in full.
+fn sample_lights(r: &Ray, rec: &HitRecord, scene: &Scene, with_mis: bool, rng: &mut ChaCha12Rng) -> Option<Color> {
+    let area_lights = scene.objects.lights();
+    let num_lights = scene.lights.len() + area_lights.len();
+    if num_lights == 0 {
+        return None;
+    }
+    // Called with a dummy direction just to find out whether the material
+    // can be lit directly at all.
+    rec.mat.eval(r, rec, rec.normal)?;
+
+    let black = Color::new(0.0, 0.0, 0.0);
+    let light_num = rng.gen_range(0..num_lights);
+    let radiance = if light_num < scene.lights.len() {
+        // Scattered rays can never hit these lights, so there's nothing to
+        // weigh them against.
+        match scene.lights[light_num].sample(rec.p) {
+            Some(sample) => {
+                let shadow_ray = Ray::new(rec.p, sample.direction);
+                // Stops just short of the light, so points right behind it
+                // don't count.
+                let blocked = scene.objects.hit(&shadow_ray, 0.001, sample.distance * (1.0 - 1e-9)).is_some();
+                if blocked {
+                    black
+                } else {
+                    rec.mat.eval(r, rec, sample.direction).unwrap_or(black) * sample.radiance * num_lights as f64
+                }
+            }
+            None => black,
+        }
+    } else {
+        let index = area_lights[light_num - scene.lights.len()];
+        match scene.objects.object(index).sample_towards(rng, rec.p) {
+            Some(direction) => {
+                let shadow_ray = Ray::new(rec.p, direction);
+                match scene.objects.hit_with_index(&shadow_ray, 0.001, f64::INFINITY) {
+                    // Only counts if the first thing in the way is the light
+                    // itself.
+                    Some((hit_index, light_rec)) if hit_index == index => {
+                        let pdf = light_pdf(scene, index, rec.p, direction);
+                        let weight = if with_mis { power_heuristic(pdf, rec.mat.pdf(r, rec, direction)) } else { 1.0 };
+                        let emitted = light_rec.mat.emit(rng, &shadow_ray, &light_rec);
+                        weight * rec.mat.eval(r, rec, direction).unwrap_or(black) * emitted / pdf
+                    }
+                    _ => black,
+                }
+            }
+            None => black,
+        }
+    };
+
+    Some(radiance)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::background::Black;
+    use crate::bvh::BvhWorld;
+    use crate::material::{DiffuseLight, Metal};
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+
+    // The average color of many samples of a ray from origin in direction.
+    fn average_color(integrator: &dyn Integrator, origin: Point3, direction: Vec3, scene: &Scene) -> Color {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let samples = 200_000;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            sum += integrator.ray_color(Ray::new(origin, direction), scene, &mut rng);
+        }
+        sum / samples as f64
+    }
+
+    // A very fuzzy metal floor under a small light, with nothing else around,
+    // so all the light there is comes straight from the light. The
+    // Whitted integrator only finds it with shadow rays, and the path tracer
+    // weighs those against scattered rays, so they should agree, even though
+    // a lot of the metal's scattered rays end up below the floor.
+    #[test]
+    fn fuzzy_metal_is_lit_the_same_with_and_without_mis() {
+        let scene = Scene {
+            camera: Default::default(),
+            named_objects: Default::default(),
+            objects: BvhWorld::new(vec![
+                Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.8)))),
+                Box::new(Sphere::new(Point3::new(1.5, 1.0, 0.0), 0.2, Arc::new(DiffuseLight::new(Color::new(10.0, 10.0, 10.0))))),
+            ]),
+            background: Arc::new(Black {}),
+            lights: Vec::new(),
+        };
+        let (origin, direction) = (Point3::new(-2.0, 1.0, 0.0), Vec3::new(2.0, -1.0, 0.0));
+
+        let with_mis = average_color(&PathTracer::new(5, 50), origin, direction, &scene);
+        let without_mis = average_color(&Whitted::new(50), origin, direction, &scene);
+        assert!(without_mis.x() > 0.0);
+        assert!((with_mis.x() / without_mis.x() - 1.0).abs() < 0.02, "{:?} vs {:?}", with_mis, without_mis);
+    }
+}
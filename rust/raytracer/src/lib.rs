@@ -4,4 +4,27 @@ pub mod hit;
 pub mod sphere;
 pub mod camera;
 pub mod material;
-pub mod plane;
\ No newline at end of file
+pub mod plane;
+pub mod scene;
+pub mod aabb;
+pub mod bvh;
+pub mod image_writer;
+pub mod tonemap;
+pub mod color;
+pub mod background;
+pub mod image_reader;
+pub mod light;
+pub mod integrator;
+pub mod triangle;
+pub mod mesh_reader;
+pub mod mesh;
+pub mod quad;
+pub mod disk;
+pub mod cuboid;
+pub mod roots;
+pub mod cylinder;
+pub mod cone;
+pub mod capsule;
+pub mod torus;
+pub mod matrix;
+pub mod transform;
\ No newline at end of file
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use super::vec::{Color, Point3, Vec3};
+
+// Which way some light is coming from, and how much of it there is.
+pub struct LightSample {
+    // Unit vector from the point being lit towards the light.
+    pub direction: Vec3,
+    // How far the ray has to go unblocked to reach the light. Infinite for
+    // lights that are infinitely far away.
+    pub distance: f64,
+    // The light arriving at the point, already divided by the probability of
+    // having picked this direction (which is 1.0 for every light here, since
+    // they're all infinitely small).
+    pub radiance: Color,
+}
+
+// Lights that aren't objects, so rays can never hit them. The only way their
+// light gets into the image is by sampling them directly, with shadow rays.
+// Emissive objects are lights too, but those are handled by the Hit trait.
+#[typetag::serde(tag = "type")]
+pub trait Light: Send + Sync {
+    // None if p doesn't get any light from this one, no matter what's in the
+    // way.
+    fn sample(&self, p: Point3) -> Option<LightSample>;
+}
+
+// Shines the same in every direction, falling off with the square of the
+// distance.
+#[derive(Serialize, Deserialize)]
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+}
+
+impl PointLight {
+    #[allow(unused)]
+    pub fn new(position: Point3, intensity: Color) -> PointLight {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Light for PointLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / (distance * distance),
+        })
+    }
+}
+
+// A point light that only shines in a cone around direction. Both angles are
+// in degrees, from the middle of the cone: cone_angle is where the light
+// stops, and it fades out smoothly over the last falloff degrees before that.
+#[derive(Serialize, Deserialize)]
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    intensity: Color,
+    cone_angle: f64,
+    #[serde(default)]
+    falloff: f64,
+}
+
+impl SpotLight {
+    #[allow(unused)]
+    pub fn new(position: Point3, direction: Vec3, intensity: Color, cone_angle: f64, falloff: f64) -> SpotLight {
+        SpotLight {
+            position,
+            direction,
+            intensity,
+            cone_angle,
+            falloff,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Light for SpotLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+
+        let cos_angle = (-1.0 * direction).dot(self.direction.normalized());
+        let cos_outer = self.cone_angle.to_radians().cos();
+        let cos_inner = (self.cone_angle - self.falloff).max(0.0).to_radians().cos();
+        if cos_angle <= cos_outer {
+            return None;
+        }
+        let strength = if cos_angle >= cos_inner {
+            1.0
+        } else {
+            // Smoothstep.
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        };
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: strength * self.intensity / (distance * distance),
+        })
+    }
+}
+
+// Light from infinitely far away, like the sun, all going in the same
+// direction. irradiance is how much of it lands on a surface facing it head
+// on.
+#[derive(Serialize, Deserialize)]
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    #[allow(unused)]
+    pub fn new(direction: Vec3, irradiance: Color) -> DirectionalLight {
+        DirectionalLight {
+            direction,
+            irradiance,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Light for DirectionalLight {
+    fn sample(&self, _p: Point3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -1.0 * self.direction.normalized(),
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
@@ -5,45 +5,47 @@ mod sphere;
 mod camera;
 mod material;
 mod plane;
-
-use std::{fs::File, io::{self, stderr, BufReader, BufWriter, Write}, thread::{self, JoinHandle}, time::Duration};
+mod scene;
+mod aabb;
+mod bvh;
+mod image_writer;
+mod tonemap;
+mod color;
+mod background;
+mod image_reader;
+mod light;
+mod integrator;
+mod triangle;
+mod mesh_reader;
+mod mesh;
+mod quad;
+mod disk;
+mod cuboid;
+mod roots;
+mod cylinder;
+mod cone;
+mod capsule;
+mod torus;
+mod matrix;
+mod transform;
+
+use std::{fs::File, io::{self, stderr, BufReader, BufWriter, Write}, ops::Range, sync::{atomic::{AtomicU64, Ordering}, Arc}, thread::{self, JoinHandle}};
 use clap_serde_derive::{clap::{self, error::ErrorKind, CommandFactory as _, Parser}, ClapSerde};
 use serde::{Serialize, Deserialize};
 use rand::{Rng, SeedableRng};
 
 use rand_chacha::ChaCha12Rng;
-use vec::{Vec3, Color};
-use ray::Ray;
-use hit::{Hit, World};
-use camera::Camera;
+use vec::{Vec3, Color, Point3};
+use camera::{CameraSettings, Projection};
+use scene::Scene;
+use image_writer::{ImageFormat, PixelGrid};
+use tonemap::{ToneMapper, ToneMapping};
+use color::TransferFunction;
+use background::Background;
+use integrator::{Albedo, AmbientOcclusion, Depth, Integrator, IntegratorKind, Normals, PathTracer, Whitted};
 
 const DEFAULT_NUM_THREADS: u64 = 8;
-
-// Gets a color from each ray that forms a gradient when put together in the
-// viewport.
-// Because the ray is normalized first, there is a slight horizontal gradient
-// from light blue on the left, through white, and to light blue on the right.
-// Basically, the x stole from the y when it was pointing left and pointing
-// right. This is why the image is pretty :).
-fn ray_color(r: &Ray, world: &World, depth: u64, rng: &mut ChaCha12Rng) -> Color {
-    if depth == 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        if let Some((attenuation, scattered)) = rec.mat.scatter(rng, r, &rec) {
-            rec.mat.emit(rng, r, &rec) + attenuation * ray_color(&scattered, world, depth - 1, rng)
-        }
-        else {
-            Color::new(0.0, 0.0, 0.0)
-        }
-    } else {
-        // Color::new(0.0, 0.0, 0.0)
-        let unit_direction = r.direction().normalized();
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-    }
-}
+const DEFAULT_TILE_SIZE: u64 = 16;
 
 #[derive(Parser)]
 struct Cli {
@@ -75,17 +77,82 @@ struct Config {
     /// Samples per pixel [default: 100]
     #[arg(short, long)]
     samples_per_pixel: u64,
-    /// Max bounce depth [default: 5]
+    /// How to turn rays into colors. Everything but path is for debugging
+    /// [default: path]
+    #[arg(short = 'i', long, value_enum)]
+    integrator: IntegratorKind,
+    /// How far the ambient-occlusion integrator looks for things in the way
+    /// [default: no limit]
+    #[arg(long)]
+    ao_distance: Option<f64>,
+    /// Bounces before Russian roulette can start ending paths [default: 3]
+    #[arg(long = "min-bounces")]
+    min_depth: u64,
+    /// Most bounces a path can ever take. Russian roulette ends most paths
+    /// well before this [default: 50]
     #[arg(short = 'b', long = "bounces")]
     max_depth: u64,
     /// Path of the file to output to. If not specified, defaults to standard output.
     #[arg(short, long)]
     output_path: Option<std::path::PathBuf>,
+    /// Format of the output image [default: guessed from the output path's
+    /// extension, or ppm for standard output]
+    #[arg(short, long, value_enum)]
+    format: Option<ImageFormat>,
+    /// How to fit bright colors into 8-bit images. Doesn't affect high dynamic
+    /// range formats [default: clamp]
+    #[arg(long, value_enum)]
+    tone_mapper: ToneMapper,
+    /// Exposure adjustment in stops (EV), applied before tone mapping. Doesn't
+    /// affect high dynamic range formats [default: 0]
+    #[arg(short, long, allow_negative_numbers = true)]
+    exposure: f64,
+    /// Luminance that maps to pure white with the extended-reinhard tone
+    /// mapper [default: 4]
+    #[arg(long)]
+    white_point: f64,
+    /// How to encode the linear colors for 8-bit images. High dynamic range
+    /// formats are always linear [default: srgb]
+    #[arg(long, value_enum)]
+    transfer_function: TransferFunction,
     /// Random seed to use throughout the program, mostly for ray bounces.
     #[arg(short = 'R', long)]
     random_seed: u64,
     #[arg(short = 't', long)]
     num_threads: u64,
+    /// Width and height of the square tiles the image is split into for the
+    /// threads to work on [default: 16]
+    #[arg(short = 'T', long)]
+    tile_size: u64,
+    // The camera settings override the ones in the scene file, if it has any.
+
+    /// Position of the camera, as "x,y,z" [default: 0,0,0]
+    #[arg(long, allow_hyphen_values = true)]
+    look_from: Option<Point3>,
+    /// Point the camera looks towards, as "x,y,z" [default: 0,0,-1]
+    #[arg(long, allow_hyphen_values = true)]
+    look_at: Option<Point3>,
+    /// Which way is up for the camera, as "x,y,z" [default: 0,1,0]
+    #[arg(long, allow_hyphen_values = true)]
+    vup: Option<Vec3>,
+    /// Vertical field of view, in degrees [default: 90]
+    #[arg(long = "vfov")]
+    vertical_fov: Option<f64>,
+    /// Diameter of the camera's lens. 0 is a pinhole camera, with everything
+    /// in focus [default: 0]
+    #[arg(long)]
+    aperture: Option<f64>,
+    /// Distance from the camera to the plane that's in perfect focus
+    /// [default: the distance from look_from to look_at]
+    #[arg(long)]
+    focus_dist: Option<f64>,
+    /// How the camera projects the scene onto the image [default: perspective]
+    #[arg(long, value_enum)]
+    projection: Option<Projection>,
+    // Overrides the scene's background. Only settable from the config file,
+    // since it's an object like {"type": "SolidColor", "color": {"e": [...]}}.
+    #[arg(skip)]
+    background: Option<Arc<dyn Background>>,
     /// Only required if no config is specified.
     #[arg(required_unless_present("config_path"))]
     world_path: Option<std::path::PathBuf>,
@@ -132,17 +199,43 @@ fn get_aspect_ratio_and_resolution(aspect_ratio: Option<f64>, width: Option<u64>
     }
 }
 
-type PixelGrid = Vec<Vec<Color>>;
+// A rectangle of the image, rendered by one thread in one go.
+struct Tile {
+    // Pixel coordinates, with rows counting up from the bottom of the image
+    // like v does.
+    columns: Range<u64>,
+    rows: Range<u64>,
+    // Row by row, starting from rows.start.
+    pixels: Vec<Color>,
+}
+
 fn main() -> io::Result<()> {
     let default_config = Config {
         aspect_ratio: None,
         image_width: None,
         image_height: None,
         samples_per_pixel: 100,
-        max_depth: 5,
+        integrator: IntegratorKind::Path,
+        ao_distance: None,
+        min_depth: 3,
+        max_depth: 50,
         output_path: None,
+        format: None,
+        tone_mapper: ToneMapper::Clamp,
+        exposure: 0.0,
+        white_point: 4.0,
+        transfer_function: TransferFunction::Srgb,
         random_seed: 0,
         num_threads: DEFAULT_NUM_THREADS,
+        tile_size: DEFAULT_TILE_SIZE,
+        look_from: None,
+        look_at: None,
+        vup: None,
+        vertical_fov: None,
+        aperture: None,
+        focus_dist: None,
+        projection: None,
+        background: None,
         world_path: None,
     };
 
@@ -168,24 +261,62 @@ fn main() -> io::Result<()> {
         }
     };
 
+    if config.tile_size == 0 {
+        Cli::command().error(ErrorKind::ValueValidation, "The tile size must be at least 1.").exit();
+    }
+
     let (aspect_ratio, res) = get_aspect_ratio_and_resolution(config.aspect_ratio, config.image_width, config.image_height);
 
+    // Scene
+    // Loaded before creating the output file, so that a broken scene doesn't
+    // clobber the last image. clap only makes sure there's a world path when
+    // there's no config file, so the config file might not have had one.
+    let world_path = match config.world_path {
+        Some(ref world_path) => world_path,
+        None => Cli::command().error(ErrorKind::MissingRequiredArgument, "No world path was specified.").exit(),
+    };
+    let mut scene = Scene::load(world_path)?;
+    if let Some(ref background) = config.background {
+        scene.background = Arc::clone(background);
+    }
+    let scene = Arc::new(scene);
+
+    // Camera
+    let cam = Arc::new(CameraSettings {
+        look_from: config.look_from,
+        look_at: config.look_at,
+        vup: config.vup,
+        vertical_fov: config.vertical_fov,
+        aperture: config.aperture,
+        focus_dist: config.focus_dist,
+        projection: config.projection,
+    }.or(&scene.camera).to_camera(aspect_ratio));
+
+    let integrator: Arc<dyn Integrator> = match config.integrator {
+        IntegratorKind::Path => Arc::new(PathTracer::new(config.min_depth, config.max_depth)),
+        IntegratorKind::Normals => Arc::new(Normals),
+        IntegratorKind::Depth => Arc::new(Depth),
+        IntegratorKind::Albedo => Arc::new(Albedo),
+        IntegratorKind::AmbientOcclusion => Arc::new(AmbientOcclusion::new(config.ao_distance.unwrap_or(f64::INFINITY))),
+        IntegratorKind::Whitted => Arc::new(Whitted::new(config.max_depth)),
+    };
+
+    let format = match (config.format, &config.output_path) {
+        (Some(format), _) => format,
+        (None, None) => ImageFormat::Ppm,
+        (None, Some(output_path)) => match ImageFormat::from_extension(output_path) {
+            Some(format) => format,
+            None => Cli::command().error(
+                ErrorKind::ValueValidation,
+                format!("Can't tell what format to write {} in from its extension. Try --format.", output_path.display()),
+            ).exit(),
+        },
+    };
+
     // Following this code: https://users.rust-lang.org/t/write-to-stdout-stderr-or-file/29739
     let mut output: Box<dyn io::Write> = match config.output_path {
         None => Box::new(io::stdout()),
-        Some(ref output_path) => {
-            let extension_error = || {
-                panic!("The output path specified, {}, does not end in .ppm.", output_path.to_str()
-                    .expect("The output path was not valid UTF-8."));
-            };
-            let Some(extension) = output_path.extension() else {
-                extension_error()
-            };
-            if extension != "ppm" {
-                extension_error()
-            }
-            Box::new(BufWriter::new(File::create(output_path)?))
-        }
+        Some(ref output_path) => Box::new(BufWriter::new(File::create(output_path)?)),
     };
 
     if !args.quiet {
@@ -202,38 +333,52 @@ fn main() -> io::Result<()> {
     }
 
 
-    let join_handles = (0..config.num_threads).map(|thread_num| {
-        let config = config.clone();
-        
-        thread::spawn(move || {
-
-            // For more info on ANSI codes:
-            // https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797
-            // TODO: Make the cursor not blink! (Probably by printing the escape
-            // codes before and after the entire duration of the threads). The
-            // escape code listed on the above gist doesn't seem to work on
-            // either macOS Terminal or VSCode's Terminal.
-            let offset_ansi_code = format!("\x1B[{}G", thread_num * 7 + 1);
-            let height_of_portion = res.height / config.num_threads;
+    // The image is split into tiles, which the threads take from a shared
+    // queue (really just a counter) until there are none left. Threads that
+    // get cheap tiles, like ones that are all sky, just go back for more.
+    let tiles_across = res.width.div_ceil(config.tile_size);
+    let tiles_down = res.height.div_ceil(config.tile_size);
+    let num_tiles = tiles_across * tiles_down;
+    let next_tile = Arc::new(AtomicU64::new(0));
+    let tiles_done = Arc::new(AtomicU64::new(0));
 
-            let starting_height = thread_num * height_of_portion;
-            let ending_height = (thread_num + 1) * height_of_portion;
-
-            println!("Thread {thread_num} - Starting height: {starting_height:4}, Ending height: {ending_height:4}");
-            thread::sleep(Duration::from_millis(200));
-
-            // World
-            let world = serde_json::from_reader(BufReader::new(File::open(config.world_path.unwrap()).unwrap())).unwrap();
-
-            // Camera
-            let cam = Camera::new(f64::from(aspect_ratio));
+    let join_handles = (0..config.num_threads).map(|_| {
+        let config = config.clone();
+        let scene = Arc::clone(&scene);
+        let cam = Arc::clone(&cam);
+        let integrator = Arc::clone(&integrator);
+        let next_tile = Arc::clone(&next_tile);
+        let tiles_done = Arc::clone(&tiles_done);
 
-            let mut rng = ChaCha12Rng::seed_from_u64(config.random_seed);
-            let image_portion = (starting_height..ending_height).rev().map(|j| {
-                eprint!("\r{}{:4}", offset_ansi_code, j + 1 - starting_height);
-                stderr().flush().unwrap();
+        thread::spawn(move || {
+            let seeded_rng = ChaCha12Rng::seed_from_u64(config.random_seed);
+            let mut tiles = Vec::new();
+
+            loop {
+                let tile_num = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile_num >= num_tiles {
+                    break;
+                }
+
+                // Tiles are numbered from the top left, but j counts up from
+                // the bottom of the image.
+                let columns_start = (tile_num % tiles_across) * config.tile_size;
+                let rows_end = res.height - (tile_num / tiles_across) * config.tile_size;
+                let tile = Tile {
+                    columns: columns_start..(columns_start + config.tile_size).min(res.width),
+                    rows: rows_end.saturating_sub(config.tile_size)..rows_end,
+                    pixels: Vec::new(),
+                };
+
+                let pixels = tile.rows.clone().flat_map(|j| tile.columns.clone().map(move |i| (i, j))).map(|(i, j)| {
+                    // Every pixel gets its own stream of random numbers from
+                    // the same seed. That way, no two pixels see the same
+                    // noise, and each pixel comes out the same no matter which
+                    // thread renders it or what else that thread rendered
+                    // first.
+                    let mut rng = seeded_rng.clone();
+                    rng.set_stream(j * res.width + i);
 
-                let scanline = (0..res.width).map(|i| {
                     let mut pixel_color = Vec3::new(0.0, 0.0, 0.0);
                     for _ in 0..config.samples_per_pixel {
                         let random_u_component: f64 = rng.gen();
@@ -244,36 +389,44 @@ fn main() -> io::Result<()> {
                         let v =
                             ((j as f64) + random_v_component) / ((res.height - 1) as f64);
 
-                        let r = cam.get_ray(u, v);
-                        pixel_color += ray_color(&r, &world, config.max_depth, &mut rng);
+                        let r = cam.get_ray(&mut rng, u, v);
+                        pixel_color += integrator.ray_color(r, &scene, &mut rng);
                     }
 
                     pixel_color
                 }).collect::<Vec<Color>>();
 
-                scanline
-            }).collect::<PixelGrid>();
+                tiles.push(Tile { pixels, ..tile });
 
-            eprint!("\r{}Done!", offset_ansi_code);
+                let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                eprint!("\rTiles done: {done:5} / {num_tiles}");
+                stderr().flush().unwrap();
+            }
 
-            image_portion
+            tiles
         })
 
-    }).collect::<Vec<JoinHandle<PixelGrid>>>();
+    }).collect::<Vec<JoinHandle<Vec<Tile>>>>();
 
-    // Header
-    writeln!(output, "P3")?;
-    writeln!(output, "{} {}", res.width, res.height)?;
-    writeln!(output, "255")?;
-
-    for scanline in join_handles.into_iter().rev().map(|handle| handle.join().unwrap().concat()) {
-        for pixel_color in scanline {
-            write!(output, "{} ", pixel_color.format_color(config.samples_per_pixel))?;
+    // Rows go from the top of the image down, the way they're written out.
+    let mut image: PixelGrid = vec![vec![Color::default(); res.width as usize]; res.height as usize];
+    for tile in join_handles.into_iter().flat_map(|handle| handle.join().unwrap()) {
+        let mut pixels = tile.pixels.into_iter();
+        for j in tile.rows {
+            for i in tile.columns.clone() {
+                image[(res.height - 1 - j) as usize][i as usize] = pixels.next().unwrap();
+            }
         }
-        writeln!(output)?;
     }
 
-    eprintln!(); // Print newline, to keep around final "Done!" messages.
+    let tone_mapping = ToneMapping {
+        tone_mapper: config.tone_mapper,
+        exposure: config.exposure,
+        white_point: config.white_point,
+    };
+    image_writer::write_image(&mut output, format, &image, config.samples_per_pixel, &tone_mapping, config.transfer_function)?;
+
+    eprintln!(); // Print newline, to keep around the final progress message.
 
     Ok(())
 }
@@ -1,3 +1,5 @@
+use std::f64::consts::PI;
+
 use rand::Rng;
 use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
@@ -9,15 +11,33 @@ use super::vec::{Vec3, Color};
 #[typetag::serde(tag = "type")]
 pub trait Scatter {
     fn scatter(&self, rng: &mut ChaCha12Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+    // How much of the light coming in from direction leaves along r_in,
+    // backwards (the BRDF times the cosine of the angle to the normal). This
+    // is what lets the renderer sample lights directly. None for materials
+    // that only ever scatter in exact directions, like mirrors and glass,
+    // since no light ever comes in from the direction of a light exactly.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<Color>;
+    // The probability density (per steradian) of scatter picking direction,
+    // which doesn't have to be a unit vector. Only meaningful when eval isn't
+    // None.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64;
+    // The color of the surface itself, regardless of lighting. Only used for
+    // debugging.
+    fn albedo(&self) -> Color;
 }
 
 #[typetag::serde(tag = "type")]
 pub trait Emit {
     fn emit(&self, rng: &mut ChaCha12Rng, r_in: &Ray, rec: &HitRecord) -> Color;
+    // Whether emit ever returns anything but black.
+    fn is_emissive(&self) -> bool;
 }
 
 #[typetag::serde(tag = "type")]
-pub trait Material : Scatter + Emit {}
+pub trait Material : Scatter + Emit + Send + Sync {}
+
+// All colors here (albedo, emission) are linear, with sRGB primaries. See
+// color.rs.
 
 #[derive(Serialize, Deserialize)]
 pub struct Lambertian {
@@ -82,6 +102,20 @@ impl Scatter for Lambertian {
 
         Some((self.albedo, scattered))
     }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let cos_theta = direction.normalized().dot(rec.normal).max(0.0);
+        Some(self.albedo / PI * cos_theta)
+    }
+
+    // The normal plus a random unit vector comes out cosine-weighted.
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        direction.normalized().dot(rec.normal).max(0.0) / PI
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
 
 #[typetag::serde]
@@ -89,6 +123,10 @@ impl Emit for Lambertian {
     fn emit(&self, _rng: &mut ChaCha12Rng, _r_in: &Ray, _rec: &HitRecord) -> Color {
         self.emission
     }
+
+    fn is_emissive(&self) -> bool {
+        !self.emission.near_zero()
+    }
 }
 
 #[typetag::serde]
@@ -138,6 +176,48 @@ impl Scatter for Metal {
             None
         }
     }
+
+    // scatter always returns albedo as the attenuation, so this is just the
+    // albedo times the pdf, for directions above the surface. Perfect mirrors
+    // only ever reflect in one exact direction, so they can't be lit directly.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<Color> {
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+        if direction.dot(rec.normal) <= 0.0 {
+            return Some(Color::new(0.0, 0.0, 0.0));
+        }
+        Some(self.albedo * self.pdf(r_in, rec, direction))
+    }
+
+    // scatter aims for a random point in a ball of radius fuzz around the tip
+    // of the (unit) reflection direction, so the density of a direction is
+    // how much of that ball it passes through: the integral of t^2 dt along
+    // the part of the ray inside the ball, over the ball's volume.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        if self.fuzz <= 0.0 {
+            return 0.0;
+        }
+        let reflection_direction = r_in.direction().reflect(rec.normal).normalized();
+        let cos_to_reflection = direction.normalized().dot(reflection_direction);
+
+        // Where the ray goes in and out of the ball.
+        let quarter_discriminant = cos_to_reflection * cos_to_reflection - 1.0 + self.fuzz * self.fuzz;
+        if quarter_discriminant < 0.0 {
+            return 0.0;
+        }
+        let t_out = cos_to_reflection + quarter_discriminant.sqrt();
+        let t_in = (cos_to_reflection - quarter_discriminant.sqrt()).max(0.0);
+        if t_out <= 0.0 {
+            return 0.0;
+        }
+
+        (t_out.powi(3) - t_in.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
 
 #[typetag::serde]
@@ -145,7 +225,138 @@ impl Emit for Metal {
     fn emit(&self, _rng: &mut ChaCha12Rng, _r_in: &Ray, _rec: &HitRecord) -> Color {
         self.emission
     }
+
+    fn is_emissive(&self) -> bool {
+        !self.emission.near_zero()
+    }
+}
+
+#[typetag::serde]
+impl Material for Metal {}
+
+#[derive(Serialize, Deserialize)]
+pub struct Dielectric {
+    index_of_refraction: f64,
+}
+
+impl Dielectric {
+    #[allow(unused)]
+    pub fn new(index_of_refraction: f64) -> Dielectric {
+        Dielectric {
+            index_of_refraction,
+        }
+    }
+
+    // Schlick's approximation for how much light reflects off of the surface
+    // instead of refracting, at a given angle.
+    fn reflectance(cosine: f64, refraction_ratio: f64) -> f64 {
+        let r0 = ((1.0 - refraction_ratio) / (1.0 + refraction_ratio)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+}
+
+#[typetag::serde]
+impl Scatter for Dielectric {
+    fn scatter(&self, rng: &mut ChaCha12Rng, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        // Glass absorbs nothing.
+        let attenuation = Color::new(1.0, 1.0, 1.0);
+
+        // Assumes the outside of every object is air (or, close enough, a
+        // vacuum), with an index of refraction of 1.0.
+        let refraction_ratio = if rec.front_face {
+            1.0 / self.index_of_refraction
+        } else {
+            self.index_of_refraction
+        };
+
+        let unit_direction = r_in.direction().normalized();
+        let cos_theta = (-1.0 * unit_direction).dot(rec.normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        // Past the critical angle, Snell's law has no solution, so all the
+        // light reflects (total internal reflection).
+        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        let direction = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>() {
+            unit_direction.reflect(rec.normal)
+        } else {
+            unit_direction.refract(rec.normal, refraction_ratio)
+        };
+
+        Some((attenuation, Ray::new(rec.p, direction)))
+    }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Option<Color> {
+        None
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn albedo(&self) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[typetag::serde]
+impl Emit for Dielectric {
+    fn emit(&self, _rng: &mut ChaCha12Rng, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+}
+
+#[typetag::serde]
+impl Material for Dielectric {}
+
+// A light, and nothing but. Every ray that hits it ends there.
+#[derive(Serialize, Deserialize)]
+pub struct DiffuseLight {
+    emission: Color,
+}
+
+impl DiffuseLight {
+    #[allow(unused)]
+    pub fn new(emission: Color) -> DiffuseLight {
+        DiffuseLight {
+            emission,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Scatter for DiffuseLight {
+    fn scatter(&self, _rng: &mut ChaCha12Rng, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Option<Color> {
+        None
+    }
+
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // It doesn't reflect anything.
+    fn albedo(&self) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
+#[typetag::serde]
+impl Emit for DiffuseLight {
+    fn emit(&self, _rng: &mut ChaCha12Rng, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        self.emission
+    }
+
+    fn is_emissive(&self) -> bool {
+        !self.emission.near_zero()
+    }
 }
 
 #[typetag::serde]
-impl Material for Metal {}
\ No newline at end of file
+impl Material for DiffuseLight {}
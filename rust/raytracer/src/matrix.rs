@@ -0,0 +1,152 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use serde::{Deserialize, Serialize};
+
+use super::vec::{Point3, Vec3};
+
+// A 4x4 matrix, stored row by row, for transforming points and vectors in
+// homogeneous coordinates: points get a 1.0 as their fourth component, so
+// they get translated, and vectors get a 0.0, so they don't.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Mat4 {
+    e: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(e: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 {
+            e,
+        }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        Mat4::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Counterclockwise by degrees, when looking down axis towards the origin
+    // (Rodrigues' rotation formula).
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let a = axis.normalized();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let c = 1.0 - cos;
+        Mat4::new([
+            [cos + x * x * c, x * y * c - z * sin, x * z * c + y * sin, 0.0],
+            [y * x * c + z * sin, cos + y * y * c, y * z * c - x * sin, 0.0],
+            [z * x * c - y * sin, z * y * c + x * sin, cos + z * z * c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut transposed = *self;
+        for row in 0..4 {
+            for column in 0..4 {
+                transposed[row][column] = self[column][row];
+            }
+        }
+        transposed
+    }
+
+    // Gauss-Jordan elimination, picking the biggest pivot in each column to
+    // keep the rounding errors down. None if the matrix is singular (or too
+    // close to it to tell), like a scaling by 0.0.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut m = *self;
+        let mut inverse = Mat4::identity();
+
+        for column in 0..4 {
+            let pivot_row = (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+            if m[pivot_row][column].abs() < 1e-12 {
+                return None;
+            }
+            m.e.swap(column, pivot_row);
+            inverse.e.swap(column, pivot_row);
+
+            let pivot = m[column][column];
+            for i in 0..4 {
+                m[column][i] /= pivot;
+                inverse[column][i] /= pivot;
+            }
+
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = m[row][column];
+                for i in 0..4 {
+                    m[row][i] -= factor * m[column][i];
+                    inverse[row][i] -= factor * inverse[column][i];
+                }
+            }
+        }
+
+        Some(inverse)
+    }
+
+    // Whether the bottom row is 0 0 0 1, so that points don't need to be
+    // divided by their fourth component afterwards. Every combination of
+    // translations, rotations, and scalings is.
+    pub fn is_affine(&self) -> bool {
+        self[3] == [0.0, 0.0, 0.0, 1.0]
+    }
+
+    // Only the affine part of the matrix is used (see is_affine).
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self[0][3], self[1][3], self[2][3])
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let row = |i: usize| Vec3::new(self[i][0], self[i][1], self[i][2]).dot(v);
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Mat4 {
+        Mat4::identity()
+    }
+}
+
+impl Index<usize> for Mat4 {
+    type Output = [f64; 4];
+
+    fn index(&self, row: usize) -> &[f64; 4] {
+        &self.e[row]
+    }
+}
+
+impl IndexMut<usize> for Mat4 {
+    fn index_mut(&mut self, row: usize) -> &mut [f64; 4] {
+        &mut self.e[row]
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut product = Mat4::new([[0.0; 4]; 4]);
+        for row in 0..4 {
+            for column in 0..4 {
+                product[row][column] = (0..4).map(|i| self[row][i] * other[i][column]).sum();
+            }
+        }
+        product
+    }
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::aabb::Aabb;
+use super::bvh::Bvh;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::mesh_reader::MeshData;
+use super::ray::Ray;
+use super::sphere::Sphere;
+use super::triangle::{intersect_triangle, triangle_bounding_box, triangle_collides_with_sphere, triangle_hit_record};
+use super::vec::{Point3, Vec3};
+
+// What a Mesh looks like in a scene file.
+#[derive(Serialize, Deserialize)]
+struct MeshSettings {
+    // Relative paths are relative to the directory the raytracer is run from,
+    // same as world_path.
+    path: PathBuf,
+    mat: Arc<dyn Material>,
+}
+
+// A model made of triangles, loaded from a file along with the scene. Its
+// triangles share their vertices, and have a Bvh of their own, so a mesh is
+// just one object in the scene's Bvh no matter how many triangles it has.
+#[derive(Deserialize)]
+#[serde(try_from = "MeshSettings")]
+pub struct Mesh {
+    settings: MeshSettings,
+    data: MeshData,
+    bvh: Bvh,
+    bbox: Aabb,
+}
+
+impl TryFrom<MeshSettings> for Mesh {
+    type Error = io::Error;
+
+    fn try_from(settings: MeshSettings) -> io::Result<Mesh> {
+        let data = MeshData::load(&settings.path)?;
+        if data.triangles.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: The mesh has no triangles.", settings.path.display()),
+            ));
+        }
+
+        let boxes = data
+            .triangles
+            .iter()
+            .map(|triangle| triangle_bounding_box(triangle.positions.map(|position| data.positions[position])))
+            .collect::<Vec<Aabb>>();
+        let bbox = boxes.iter().skip(1).fold(boxes[0], |bbox, other| bbox.surrounding(other));
+
+        Ok(Mesh {
+            settings,
+            bvh: Bvh::new(&boxes, (0..boxes.len()).collect()),
+            data,
+            bbox,
+        })
+    }
+}
+
+// Written back out the way it was read in, as a path, not as triangles.
+impl Serialize for Mesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.settings.serialize(serializer)
+    }
+}
+
+impl Mesh {
+    fn vertices(&self, index: usize) -> [Point3; 3] {
+        self.data.triangles[index].positions.map(|position| self.data.positions[position])
+    }
+
+    fn normals(&self, index: usize) -> Option<[Vec3; 3]> {
+        self.data.triangles[index].normals.map(|normals| normals.map(|normal| self.data.normals[normal]))
+    }
+}
+
+#[typetag::serde]
+impl Hit for Mesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit_triangle = |index: usize, r: &Ray, t_min: f64, t_max: f64| {
+            let vertices = self.vertices(index);
+            let (t, u, v) = intersect_triangle(r, t_min, t_max, vertices)?;
+            Some(triangle_hit_record(r, t, u, v, vertices, self.normals(index), self.settings.mat.as_ref()))
+        };
+
+        self.bvh.hit(r, t_min, t_max, None, &hit_triangle).map(|(_, rec)| rec)
+    }
+
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        (0..self.data.triangles.len()).any(|index| triangle_collides_with_sphere(self.vertices(index), other))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
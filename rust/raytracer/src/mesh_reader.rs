@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use super::vec::{Point3, Vec3};
+
+// One triangle of a mesh, as indices into the mesh's vertex data.
+pub struct MeshTriangle {
+    pub positions: [usize; 3],
+    // Only if every corner of the triangle has a normal.
+    pub normals: Option<[usize; 3]>,
+}
+
+// Triangles that share their vertices, the way they come out of model files.
+// Every index is checked against the vertex data while loading.
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub triangles: Vec<MeshTriangle>,
+}
+
+impl MeshData {
+    // Reads a Wavefront OBJ (.obj), Stanford PLY (.ply), or STL (.stl) file,
+    // going by the extension.
+    pub fn load(path: &Path) -> io::Result<MeshData> {
+        let with_path = |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+
+        let read: fn(BufReader<File>) -> io::Result<MeshData> = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("obj") => read_obj,
+            Some("ply") => read_ply,
+            Some("stl") => read_stl,
+            _ => return Err(with_path(invalid_data("Only .obj, .ply and .stl meshes are supported."))),
+        };
+        read(BufReader::new(File::open(path).map_err(with_path)?)).map_err(with_path)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Only the geometry: vertices (v), vertex normals (vn), and faces (f), which
+// can be any convex polygon, and get split into triangles. Texture
+// coordinates, groups, materials and everything else are skipped.
+fn read_obj(reader: impl BufRead) -> io::Result<MeshData> {
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        triangles: Vec::new(),
+    };
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let at_line = |message: String| invalid_data(format!("line {}: {}", line_index + 1, message));
+
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => mesh.positions.push(parse_obj_vec3(&mut words).map_err(at_line)?),
+            Some("vn") => mesh.normals.push(parse_obj_vec3(&mut words).map_err(at_line)?),
+            Some("f") => {
+                let corners = words
+                    .map(|corner| parse_obj_corner(corner, mesh.positions.len(), mesh.normals.len()))
+                    .collect::<Result<Vec<(usize, Option<usize>)>, String>>()
+                    .map_err(at_line)?;
+                if corners.len() < 3 {
+                    return Err(at_line(format!("A face needs at least 3 corners, but this one has {}.", corners.len())));
+                }
+
+                mesh.triangles.extend(fan(&corners).map(|[a, b, c]| MeshTriangle {
+                    positions: [a.0, b.0, c.0],
+                    normals: match (a.1, b.1, c.1) {
+                        (Some(na), Some(nb), Some(nc)) => Some([na, nb, nc]),
+                        _ => None,
+                    },
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn parse_obj_vec3<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    let mut component = || -> Result<f64, String> {
+        let word = words.next().ok_or("Expected 3 coordinates.")?;
+        word.parse::<f64>().map_err(|err| format!("{:?} is not a number: {}", word, err))
+    };
+    Ok(Vec3::new(component()?, component()?, component()?))
+}
+
+// A corner of a face is v, v/vt, v//vn or v/vt/vn, where each is a 1-based
+// index, or a negative one counting back from the latest vertex. Returns the
+// 0-based position and normal indices.
+fn parse_obj_corner(corner: &str, num_positions: usize, num_normals: usize) -> Result<(usize, Option<usize>), String> {
+    let resolve = |index: &str, count: usize, what: &str| -> Result<usize, String> {
+        let index = index.parse::<i64>().map_err(|err| format!("{:?} is not a {} index: {}", index, what, err))?;
+        let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+        if index == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("There's no {} number {} (there are only {} so far).", what, index, count));
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut indices = corner.split('/');
+    let position = resolve(indices.next().unwrap_or_default(), num_positions, "vertex")?;
+    let normal = match indices.nth(1) {
+        Some(normal) if !normal.is_empty() => Some(resolve(normal, num_normals, "normal")?),
+        _ => None,
+    };
+    Ok((position, normal))
+}
+
+// Splits a polygon into a fan of triangles, from its first corner. Corners
+// are whatever the format has for each one, like just a vertex index, or
+// (for OBJ) a vertex index and maybe a normal index.
+fn fan<T: Copy>(corners: &[T]) -> impl Iterator<Item = [T; 3]> + '_ {
+    (1..corners.len().saturating_sub(1)).map(move |i| [corners[0], corners[i], corners[i + 1]])
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, String> {
+        match name {
+            "char" | "int8" => Ok(PlyType::Int8),
+            "uchar" | "uint8" => Ok(PlyType::UInt8),
+            "short" | "int16" => Ok(PlyType::Int16),
+            "ushort" | "uint16" => Ok(PlyType::UInt16),
+            "int" | "int32" => Ok(PlyType::Int32),
+            "uint" | "uint32" => Ok(PlyType::UInt32),
+            "float" | "float32" => Ok(PlyType::Float32),
+            "double" | "float64" => Ok(PlyType::Float64),
+            _ => Err(format!("Unknown property type {:?}.", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::Int8 | PlyType::UInt8 => 1,
+            PlyType::Int16 | PlyType::UInt16 => 2,
+            PlyType::Int32 | PlyType::UInt32 | PlyType::Float32 => 4,
+            PlyType::Float64 => 8,
+        }
+    }
+}
+
+enum PlyProperty {
+    Scalar(PlyType),
+    // A count, and then that many items.
+    List(PlyType, PlyType),
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<(String, PlyProperty)>,
+}
+
+// Everything after the header, read one value at a time. Every value comes
+// out as an f64, which can hold any of the PLY types exactly.
+struct PlyBody {
+    data: Vec<u8>,
+    position: usize,
+    format: PlyFormat,
+}
+
+impl PlyBody {
+    fn read(&mut self, ty: PlyType) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            let data = &self.data[self.position..];
+            let start = data.iter().position(|byte| !byte.is_ascii_whitespace()).ok_or("The file ends too early.")?;
+            let length = data[start..].iter().position(|byte| byte.is_ascii_whitespace()).unwrap_or(data.len() - start);
+            self.position += start + length;
+
+            let word = String::from_utf8_lossy(&data[start..start + length]);
+            return word.parse::<f64>().map_err(|err| format!("{:?} is not a number: {}", word, err));
+        }
+
+        let bytes = self.data.get(self.position..self.position + ty.size()).ok_or("The file ends too early.")?;
+        self.position += ty.size();
+        let big_endian = self.format == PlyFormat::BinaryBigEndian;
+        macro_rules! from_bytes {
+            ($t:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                (if big_endian { <$t>::from_be_bytes(bytes) } else { <$t>::from_le_bytes(bytes) }) as f64
+            }};
+        }
+        Ok(match ty {
+            PlyType::Int8 => from_bytes!(i8),
+            PlyType::UInt8 => from_bytes!(u8),
+            PlyType::Int16 => from_bytes!(i16),
+            PlyType::UInt16 => from_bytes!(u16),
+            PlyType::Int32 => from_bytes!(i32),
+            PlyType::UInt32 => from_bytes!(u32),
+            PlyType::Float32 => from_bytes!(f32),
+            PlyType::Float64 => from_bytes!(f64),
+        })
+    }
+
+    // Reads a whole property, as one value, or as a list of them.
+    fn read_property(&mut self, property: &PlyProperty) -> Result<Vec<f64>, String> {
+        match *property {
+            PlyProperty::Scalar(ty) => Ok(vec![self.read(ty)?]),
+            PlyProperty::List(count_type, item_type) => {
+                let count = self.read(count_type)?;
+                if count < 0.0 || count.fract() != 0.0 {
+                    return Err(format!("{} isn't a valid list length.", count));
+                }
+                (0..count as usize).map(|_| self.read(item_type)).collect()
+            }
+        }
+    }
+}
+
+// Stanford's polygon format, in ASCII or binary. Only the vertex element
+// (with x, y, z, and optionally nx, ny, nz) and the face element (with a
+// vertex_indices or vertex_index list) are used. Everything else, including
+// other elements, is read and skipped.
+// See https://paulbourke.net/dataformats/ply/ for the format.
+fn read_ply(mut reader: impl BufRead) -> io::Result<MeshData> {
+    let mut line = String::new();
+    let mut line_number = 0;
+    let mut next_line = |line: &mut String| -> io::Result<usize> {
+        line.clear();
+        line_number += 1;
+        if reader.read_line(line)? == 0 {
+            return Err(invalid_data("The header never ends."));
+        }
+        Ok(line_number)
+    };
+
+    next_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(invalid_data("Not a PLY file (it doesn't start with \"ply\")."));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    loop {
+        let line_number = next_line(&mut line)?;
+        let at_line = |message: String| invalid_data(format!("line {}: {}", line_number, message));
+
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        match words[..] {
+            ["end_header"] => break,
+            ["format", name, _version] => {
+                format = Some(match name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(at_line(format!("Unknown format {:?}.", name))),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| at_line(format!("{:?} is not an element count.", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => {
+                let property = PlyProperty::List(PlyType::parse(count_type).map_err(at_line)?, PlyType::parse(item_type).map_err(at_line)?);
+                elements
+                    .last_mut()
+                    .ok_or_else(|| at_line("A property has to come after an element.".to_string()))?
+                    .properties
+                    .push((name.to_string(), property));
+            }
+            ["property", ty, name] => {
+                let property = PlyProperty::Scalar(PlyType::parse(ty).map_err(at_line)?);
+                elements
+                    .last_mut()
+                    .ok_or_else(|| at_line("A property has to come after an element.".to_string()))?
+                    .properties
+                    .push((name.to_string(), property));
+            }
+            _ => return Err(at_line(format!("Can't make sense of {:?}.", line.trim()))),
+        }
+    }
+
+    let mut body = PlyBody {
+        data: Vec::new(),
+        position: 0,
+        format: format.ok_or_else(|| invalid_data("The header doesn't say what format the file is in."))?,
+    };
+    reader.read_to_end(&mut body.data)?;
+
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        triangles: Vec::new(),
+    };
+    let mut faces = Vec::new();
+    for element in &elements {
+        let property_index = |name: &str| element.properties.iter().position(|(property_name, _)| property_name == name);
+
+        for i in 0..element.count {
+            let at_element = |message: String| invalid_data(format!("{} {}: {}", element.name, i, message));
+            let values = element
+                .properties
+                .iter()
+                .map(|(_, property)| body.read_property(property))
+                .collect::<Result<Vec<Vec<f64>>, String>>()
+                .map_err(at_element)?;
+
+            match element.name.as_str() {
+                "vertex" => {
+                    // None if the element doesn't have that property at all.
+                    // Properties can be lists, even when they shouldn't be,
+                    // so this takes the first item, if there is one.
+                    let component = |name: &str| -> io::Result<Option<f64>> {
+                        match property_index(name) {
+                            Some(index) => match values[index].first() {
+                                Some(&value) => Ok(Some(value)),
+                                None => Err(at_element(format!("{} is an empty list.", name))),
+                            },
+                            None => Ok(None),
+                        }
+                    };
+                    let vec3 = |[x, y, z]: [&str; 3]| -> io::Result<Option<Vec3>> {
+                        match [component(x)?, component(y)?, component(z)?] {
+                            [Some(x), Some(y), Some(z)] => Ok(Some(Vec3::new(x, y, z))),
+                            _ => Ok(None),
+                        }
+                    };
+                    mesh.positions.push(vec3(["x", "y", "z"])?.ok_or_else(|| at_element("Vertices need x, y and z.".to_string()))?);
+                    if let Some(normal) = vec3(["nx", "ny", "nz"])? {
+                        mesh.normals.push(normal);
+                    }
+                }
+                "face" => {
+                    let indices = property_index("vertex_indices")
+                        .or_else(|| property_index("vertex_index"))
+                        .ok_or_else(|| at_element("Faces need a vertex_indices list.".to_string()))?;
+                    faces.push((i, values[indices].clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Only now that all the vertices have been read can the faces' indices
+    // be checked, since the faces might come first.
+    let has_normals = !mesh.positions.is_empty() && mesh.normals.len() == mesh.positions.len();
+    for (i, indices) in faces {
+        let at_face = |message: String| invalid_data(format!("face {}: {}", i, message));
+        if indices.len() < 3 {
+            return Err(at_face(format!("A face needs at least 3 corners, but this one has {}.", indices.len())));
+        }
+        let corners = indices
+            .iter()
+            .map(|&index| {
+                if index < 0.0 || index.fract() != 0.0 || index as usize >= mesh.positions.len() {
+                    Err(at_face(format!("There's no vertex number {} (there are only {}).", index, mesh.positions.len())))
+                } else {
+                    Ok(index as usize)
+                }
+            })
+            .collect::<io::Result<Vec<usize>>>()?;
+
+        mesh.triangles.extend(fan(&corners).map(|positions| MeshTriangle {
+            positions,
+            normals: has_normals.then_some(positions),
+        }));
+    }
+
+    Ok(mesh)
+}
+
+// STL, in ASCII or binary. STL files are just a list of separate triangles,
+// so identical vertices get merged here, to share them like the other formats
+// do. The facet normals are ignored, since the winding says the same thing,
+// and plenty of exporters leave them as zeros anyway.
+fn read_stl(mut reader: impl BufRead) -> io::Result<MeshData> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut mesh = MeshData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        triangles: Vec::new(),
+    };
+    let mut vertex_indices: HashMap<[u64; 3], usize> = HashMap::new();
+    let mut add_vertex = |mesh: &mut MeshData, vertex: Point3| {
+        let key = [vertex.x().to_bits(), vertex.y().to_bits(), vertex.z().to_bits()];
+        *vertex_indices.entry(key).or_insert_with(|| {
+            mesh.positions.push(vertex);
+            mesh.positions.len() - 1
+        })
+    };
+
+    // Binary files start with an 80 byte header, which can say anything,
+    // including "solid", so the only reliable tell is whether the size
+    // matches the triangle count that comes right after the header.
+    let binary_triangle_count = data.get(80..84).map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    if let Some(count) = binary_triangle_count.filter(|&count| data.len() == 84 + 50 * count) {
+        let float = |bytes: &[u8]| f32::from_le_bytes(bytes.try_into().unwrap()) as f64;
+        for triangle in data[84..].chunks_exact(50).take(count) {
+            // Each one is a normal, three vertices, and two bytes of
+            // "attributes", which nothing uses.
+            let vertices = [12, 24, 36].map(|offset| {
+                let vertex = &triangle[offset..offset + 12];
+                Point3::new(float(&vertex[0..4]), float(&vertex[4..8]), float(&vertex[8..12]))
+            });
+            let positions = vertices.map(|vertex| add_vertex(&mut mesh, vertex));
+            mesh.triangles.push(MeshTriangle {
+                positions,
+                normals: None,
+            });
+        }
+        return Ok(mesh);
+    }
+
+    if !data.starts_with(b"solid") {
+        return Err(invalid_data(format!(
+            "Not an STL file (it doesn't start with \"solid\", and it's the wrong size for a binary STL with {} triangles).",
+            binary_triangle_count.unwrap_or(0)
+        )));
+    }
+
+    // ASCII files are made of facets like:
+    //
+    // facet normal nx ny nz
+    //     outer loop
+    //         vertex x y z
+    //         vertex x y z
+    //         vertex x y z
+    //     endloop
+    // endfacet
+    let text = String::from_utf8_lossy(&data);
+    let mut corners = Vec::new();
+    let mut in_loop = false;
+    for (line_index, line) in text.lines().enumerate() {
+        let at_line = |message: String| invalid_data(format!("line {}: {}", line_index + 1, message));
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("outer") => {
+                if in_loop {
+                    return Err(at_line("A loop starts before the last one ended.".to_string()));
+                }
+                in_loop = true;
+                corners.clear();
+            }
+            Some("vertex") => {
+                if !in_loop {
+                    return Err(at_line("A vertex has to be inside of a loop.".to_string()));
+                }
+                let vertex = parse_obj_vec3(&mut words).map_err(at_line)?;
+                corners.push(add_vertex(&mut mesh, vertex));
+            }
+            Some("endloop") => {
+                if !in_loop {
+                    return Err(at_line("A loop ends without having started.".to_string()));
+                }
+                if corners.len() < 3 {
+                    return Err(at_line(format!("A facet needs at least 3 vertices, but this one has {}.", corners.len())));
+                }
+                in_loop = false;
+                mesh.triangles.extend(fan(&corners).map(|positions| MeshTriangle {
+                    positions,
+                    normals: None,
+                }));
+            }
+            _ => {}
+        }
+    }
+    if in_loop {
+        return Err(invalid_data("The file ends in the middle of a facet."));
+    }
+
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply\nformat ascii 1.0\n\
+        element vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+        element face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    fn ply(body: &str) -> io::Result<MeshData> {
+        read_ply(format!("{}{}", HEADER, body).as_bytes())
+    }
+
+    #[test]
+    fn reads_a_triangle() {
+        let mesh = ply("0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n").unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.triangles.len(), 1);
+        assert_eq!(mesh.triangles[0].positions, [0, 1, 2]);
+    }
+
+    #[test]
+    fn obj_polygons_become_fans() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\nf 1 2 3 4\n";
+        let mesh = read_obj(obj.as_bytes()).unwrap();
+        let triangles = mesh.triangles.iter().map(|triangle| (triangle.positions, triangle.normals)).collect::<Vec<_>>();
+        assert_eq!(
+            triangles,
+            [([0, 1, 2], Some([0, 0, 0])), ([0, 2, 3], Some([0, 0, 0])), ([0, 1, 2], None), ([0, 2, 3], None)]
+        );
+    }
+
+    #[test]
+    fn truncated_ascii_body_is_an_error() {
+        assert!(ply("0 0 0\n1 0 0\n0 1").is_err());
+        assert!(ply("0 0 0\n1 0 0\n0 1 0\n3 0 1").is_err());
+    }
+
+    #[test]
+    fn truncated_binary_body_is_an_error() {
+        let header = "ply\nformat binary_little_endian 1.0\n\
+            element vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n";
+        let mut data = header.as_bytes().to_vec();
+        data.extend(1.0f32.to_le_bytes());
+        data.extend(2.0f32.to_le_bytes());
+        assert!(read_ply(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn bad_vertex_index_is_an_error() {
+        assert!(ply("0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n").is_err());
+        assert!(ply("0 0 0\n1 0 0\n0 1 0\n3 0 1 -1\n").is_err());
+    }
+
+    #[test]
+    fn empty_lists_are_errors() {
+        assert!(ply("0 0 0\n1 0 0\n0 1 0\n0\n").is_err());
+
+        let list_header = "ply\nformat ascii 1.0\n\
+            element vertex 1\nproperty list uchar float x\nproperty float y\nproperty float z\nend_header\n";
+        assert!(read_ply(format!("{}0 1 2\n", list_header).as_bytes()).is_err());
+        assert!(read_ply(format!("{}1 5 1 2\n", list_header).as_bytes()).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
@@ -12,18 +12,20 @@ use super::hit::Hit;
 
 use super::material::Material;
 
+use super::aabb::Aabb;
+
 use super::vec::{Point3, Vec3};
 
 #[derive(Serialize, Deserialize)]
 pub struct Plane {
     any_point: Point3,
     normal: Vec3,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
 }
 
 impl Plane {
     #[allow(unused)]
-    pub fn new(any_point: Point3, normal: Vec3, mat: Rc<dyn Material>) -> Plane {
+    pub fn new(any_point: Point3, normal: Vec3, mat: Arc<dyn Material>) -> Plane {
         Plane {
             any_point,
             normal,
@@ -34,7 +36,7 @@ impl Plane {
 
 #[typetag::serde]
 impl Hit for Plane {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let denominator = Vec3::dot(self.normal, r.direction());
         if denominator == 0.0 {
             return None;
@@ -48,7 +50,7 @@ impl Hit for Plane {
 
         let p = r.at(t);
 
-        let rec = HitRecord::with_normal_against_ray(p, t, r, self.normal, Rc::clone(&self.mat));
+        let rec = HitRecord::with_normal_against_ray(p, t, r, self.normal, self.mat.as_ref());
 
         Some(rec)
     }
@@ -56,4 +58,8 @@ impl Hit for Plane {
         // TODO: Implement this collision. *Should* only be used in generating the scene, so I *should* be fine, but still.
         false
     }
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Planes are infinite.
+        None
+    }
 }
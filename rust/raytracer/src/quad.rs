@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::sphere::Sphere;
+use super::triangle::triangle_collides_with_sphere;
+use super::vec::{Point3, Vec3};
+
+// A parallelogram, with corners at corner, corner + u, corner + u + v, and
+// corner + v. Its front is the side that u.cross(v) points out of, so the
+// corners go counterclockwise when looking at the front, same as Triangle.
+#[derive(Serialize, Deserialize)]
+pub struct Quad {
+    corner: Point3,
+    u: Vec3,
+    v: Vec3,
+    mat: Arc<dyn Material>,
+}
+
+impl Quad {
+    #[allow(unused)]
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Quad {
+        Quad {
+            corner,
+            u,
+            v,
+            mat,
+        }
+    }
+
+    // The four corners, in order around the edge.
+    pub fn corners(&self) -> [Point3; 4] {
+        [self.corner, self.corner + self.u, self.corner + self.u + self.v, self.corner + self.v]
+    }
+
+    pub fn area(&self) -> f64 {
+        self.u.cross(self.v).length()
+    }
+
+    // Where r hits the plane of the quad, if that's inside of it: returns t,
+    // along with how far along u and v the hit is, from 0.0 to 1.0.
+    fn intersect(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let n = self.u.cross(self.v);
+        let denominator = n.dot(r.direction());
+        // The ray is parallel to the quad (or the quad has no area). Scaled
+        // like the cutoff in intersect_triangle, for the same reason.
+        if denominator.abs() <= 1e-12 * n.length() * r.direction().length() {
+            return None;
+        }
+
+        let t = n.dot(self.corner - r.origin()) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // Solving p = corner + alpha * u + beta * v for alpha and beta.
+        let w = n / n.dot(n);
+        let planar = r.at(t) - self.corner;
+        let alpha = w.dot(planar.cross(self.v));
+        let beta = w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some((t, alpha, beta))
+    }
+}
+
+#[typetag::serde]
+impl Hit for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, _, _) = self.intersect(r, t_min, t_max)?;
+        let outward_normal = self.u.cross(self.v).normalized();
+        Some(HitRecord::with_normal_against_ray(r.at(t), t, r, outward_normal, self.mat.as_ref()))
+    }
+
+    // A parallelogram is just two triangles, as far as collisions go.
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        let [p0, p1, p2, p3] = self.corners();
+        triangle_collides_with_sphere([p0, p1, p2], other) || triangle_collides_with_sphere([p0, p2, p3], other)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [p0, p1, p2, p3] = self.corners();
+        let bbox = Aabb::new(p0, p0).surrounding(&Aabb::new(p1, p1)).surrounding(&Aabb::new(p2, p2)).surrounding(&Aabb::new(p3, p3));
+        Some(bbox.padded(1e-4))
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    // Picks a point uniformly over the quad's area.
+    fn sample_towards(&self, rng: &mut ChaCha12Rng, origin: Point3) -> Option<Vec3> {
+        let point = self.corner + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v;
+        let direction = point - origin;
+        if direction.near_zero() {
+            return None;
+        }
+        Some(direction.normalized())
+    }
+
+    // Picking uniformly by area has a density of 1 / area per unit of area,
+    // which is distance^2 / (cos * area) per steradian, as seen from origin.
+    fn pdf_towards(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some((t, _, _)) = self.intersect(&Ray::new(origin, direction), 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let n = self.u.cross(self.v);
+        let area = n.length();
+        let distance_squared = (t * direction.length()).powi(2);
+        let cosine = (direction.normalized().dot(n) / area).abs();
+        distance_squared / (cosine * area)
+    }
+}
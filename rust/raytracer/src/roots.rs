@@ -0,0 +1,113 @@
+// Real roots of polynomials, for intersecting rays with shapes. The
+// coefficients go from the highest power down, and the roots come out sorted
+// from smallest to largest.
+
+// a * x^2 + 2 * half_b * x + c = 0, written with half of b like in
+// Sphere::hit. None if there are no real roots, or a is 0.0 (so it isn't
+// really a quadratic).
+pub fn solve_quadratic(a: f64, half_b: f64, c: f64) -> Option<[f64; 2]> {
+    let quarter_discriminant = half_b * half_b - a * c;
+    if quarter_discriminant < 0.0 || a == 0.0 {
+        return None;
+    }
+
+    // The textbook formula subtracts two nearly equal numbers for one of the
+    // roots when b is much bigger than a * c, which loses most of its
+    // precision. Getting that root from the product of the roots instead
+    // (c / a) avoids that.
+    let q = -(half_b + half_b.signum() * quarter_discriminant.sqrt());
+    let (r0, r1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if r0 < r1 { [r0, r1] } else { [r1, r0] })
+}
+
+// The largest real root of x^3 + a * x^2 + b * x + c = 0. There's always at
+// least one.
+pub fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // With x = y - a / 3, this turns into y^3 + p * y + q = 0.
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    let y = if discriminant > 0.0 {
+        // Only one real root (Cardano's formula).
+        let sqrt_discriminant = discriminant.sqrt();
+        (-q / 2.0 + sqrt_discriminant).cbrt() + (-q / 2.0 - sqrt_discriminant).cbrt()
+    } else if p == 0.0 {
+        0.0
+    } else {
+        // Three real roots (the trigonometric method), of which this is the
+        // largest.
+        let cos_3_theta = (3.0 * q / (2.0 * p) * (-3.0 / p).sqrt()).clamp(-1.0, 1.0);
+        2.0 * (-p / 3.0).sqrt() * (cos_3_theta.acos() / 3.0).cos()
+    };
+    let x = y - a / 3.0;
+
+    // A couple of steps of Newton's method to clean up the rounding errors.
+    polish(x, |x| ((x + a) * x + b) * x + c, |x| (3.0 * x + 2.0 * a) * x + b)
+}
+
+// Real roots of x^4 + a * x^3 + b * x^2 + c * x + d = 0, with Ferrari's
+// method: the quartic is split into two quadratics, using a root of a cubic.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // With x = y - a / 4, this turns into y^4 + p * y^2 + q * y + r = 0.
+    let a_squared = a * a;
+    let p = b - 3.0 * a_squared / 8.0;
+    let q = c - a * b / 2.0 + a_squared * a / 8.0;
+    let r = d - a * c / 4.0 + a_squared * b / 16.0 - 3.0 * a_squared * a_squared / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    let mut push_quadratic_roots = |half_b: f64, c: f64| {
+        if let Some(roots) = solve_quadratic(1.0, half_b, c) {
+            ys.extend(roots);
+        }
+    };
+
+    // m makes (y^2 + p / 2 + m)^2 = 2 * m * y^2 - q * y + (m^2 + m * p + p^2 / 4 - r)
+    // a difference of squares, which then factors into two quadratics. It's
+    // a root of this cubic, which always has a positive one, since the cubic
+    // is negative at 0.0.
+    let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+    if m > 1e-12 {
+        let s = (2.0 * m).sqrt();
+        push_quadratic_roots(s / 2.0, p / 2.0 + m - q / (2.0 * s));
+        push_quadratic_roots(-s / 2.0, p / 2.0 + m + q / (2.0 * s));
+    } else {
+        // q is 0.0 (or close enough), so it's a quadratic in y^2.
+        if let Some(zs) = solve_quadratic(1.0, p / 2.0, r) {
+            for z in zs.into_iter().filter(|&z| z >= 0.0) {
+                ys.push(-z.sqrt());
+                ys.push(z.sqrt());
+            }
+        }
+    }
+
+    let mut xs = ys
+        .into_iter()
+        .map(|y| {
+            polish(
+                y - a / 4.0,
+                |x| (((x + a) * x + b) * x + c) * x + d,
+                |x| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c,
+            )
+        })
+        .collect::<Vec<f64>>();
+    xs.sort_by(f64::total_cmp);
+    xs
+}
+
+// Newton's method, for roots that are already close.
+fn polish(mut x: f64, f: impl Fn(f64) -> f64, derivative: impl Fn(f64) -> f64) -> f64 {
+    for _ in 0..2 {
+        let slope = derivative(x);
+        if slope == 0.0 {
+            break;
+        }
+        let next = x - f(x) / slope;
+        if !next.is_finite() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
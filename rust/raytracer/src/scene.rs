@@ -0,0 +1,245 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::background::{Background, VerticalGradient};
+use super::bvh::BvhWorld;
+use super::camera::CameraSettings;
+use super::hit::{Hit, World};
+use super::light::Light;
+
+// Everything needed to describe a shot. In JSON, this looks like:
+//
+// {
+//     "camera": { "look_from": ..., "look_at": ..., ... },
+//     "named_objects": { "teapot": { "type": "Mesh", ... }, ... },
+//     "objects": [ ... ],
+//     "background": { "type": "SolidColor", "color": { "e": [0.0, 0.0, 0.0] } },
+//     "lights": [ { "type": "PointLight", "position": ..., "intensity": ... }, ... ]
+// }
+//
+// where camera, named_objects, background and lights are optional. The default background is
+// the sky gradient. lights are only the lights that aren't objects (see
+// light.rs), since emissive objects light the scene on their own. For
+// backwards compatibility, a bare array of objects (the old world file format)
+// is also a valid scene, with the default camera and background.
+#[derive(Serialize)]
+pub struct Scene {
+    pub camera: CameraSettings,
+    #[serde(skip_serializing_if = "NamedObjects::is_empty")]
+    pub named_objects: NamedObjects,
+    pub objects: BvhWorld,
+    pub background: Arc<dyn Background>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<Box<dyn Light>>,
+}
+
+// Objects that Transforms can use by name (see TransformSettings), so that
+// something like a big mesh only gets loaded once, no matter how many places
+// it shows up in. They aren't in the scene unless something uses them.
+//
+// They're kept in the order they're written in, and each one can use the
+// ones before it, but not after, which also rules out loops.
+#[derive(Default)]
+pub struct NamedObjects(Vec<(String, Arc<dyn Hit>)>);
+
+impl NamedObjects {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+thread_local! {
+    // The named objects of the scene being read in, so far. Transforms are
+    // read in on their own, with no way to get at the rest of the scene, so
+    // this is how they find the objects they use.
+    static NAMED_OBJECTS_SO_FAR: RefCell<Vec<(String, Arc<dyn Hit>)>> = const { RefCell::new(Vec::new()) };
+}
+
+// The named object called name, in the scene being read in, if it's been
+// read in yet.
+pub fn named_object(name: &str) -> Option<Arc<dyn Hit>> {
+    NAMED_OBJECTS_SO_FAR.with_borrow(|named| named.iter().find(|(other, _)| other == name).map(|(_, object)| object.clone()))
+}
+
+impl Serialize for NamedObjects {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, object)| (name, object)))
+    }
+}
+
+impl<'de> Deserialize<'de> for NamedObjects {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<NamedObjects, D::Error> {
+        struct NamedObjectsVisitor;
+
+        impl<'de> Visitor<'de> for NamedObjectsVisitor {
+            type Value = NamedObjects;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of names to objects")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<NamedObjects, A::Error> {
+                let mut named = Vec::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    if named.iter().any(|(other, _)| *other == name) {
+                        return Err(de::Error::custom(format!("There's already an object named {:?}.", name)));
+                    }
+                    let object: Arc<dyn Hit> = map.next_value()?;
+                    NAMED_OBJECTS_SO_FAR.with_borrow_mut(|so_far| so_far.push((name.clone(), object.clone())));
+                    named.push((name, object));
+                }
+                Ok(NamedObjects(named))
+            }
+        }
+
+        deserializer.deserialize_map(NamedObjectsVisitor)
+    }
+}
+
+pub fn default_background() -> Arc<dyn Background> {
+    Arc::new(VerticalGradient::default())
+}
+
+impl Scene {
+    // Errors say which file they came from, since there's no backtrace to
+    // go on.
+    pub fn load(path: &Path) -> io::Result<Scene> {
+        let with_path = |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+
+        let file = File::open(path).map_err(with_path)?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|err| with_path(err.into()))
+    }
+}
+
+// The object form of a scene file. This is just the derived Deserialize for
+// Scene, which can't be derived directly on Scene because Scene also has to
+// handle bare arrays.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneObject {
+    #[serde(default)]
+    camera: CameraSettings,
+    #[serde(default)]
+    named_objects: NamedObjects,
+    objects: World,
+    #[serde(default = "default_background")]
+    background: Arc<dyn Background>,
+    #[serde(default)]
+    lights: Vec<Box<dyn Light>>,
+}
+
+impl From<SceneObject> for Scene {
+    fn from(scene: SceneObject) -> Scene {
+        Scene {
+            camera: scene.camera,
+            named_objects: scene.named_objects,
+            objects: BvhWorld::new(scene.objects),
+            background: scene.background,
+            lights: scene.lights,
+        }
+    }
+}
+
+impl From<World> for Scene {
+    fn from(objects: World) -> Scene {
+        Scene {
+            camera: CameraSettings::default(),
+            named_objects: NamedObjects::default(),
+            objects: BvhWorld::new(objects),
+            background: default_background(),
+            lights: Vec::new(),
+        }
+    }
+}
+
+// Not using #[serde(untagged)] for this, because its only error message is
+// "data did not match any variant", which is useless for finding a typo in a
+// world file thousands of lines long. Dispatching on the first token instead
+// keeps serde_json's error messages, with their line and column numbers.
+impl<'de> Deserialize<'de> for Scene {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Scene, D::Error> {
+        struct SceneVisitor;
+
+        impl<'de> Visitor<'de> for SceneVisitor {
+            type Value = Scene;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a scene object or an array of objects")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Scene, A::Error> {
+                World::deserialize(SeqAccessDeserializer::new(seq)).map(Scene::from)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Scene, A::Error> {
+                SceneObject::deserialize(MapAccessDeserializer::new(map)).map(Scene::from)
+            }
+        }
+
+        // Each scene starts out with no named objects, and doesn't leave its
+        // own around afterwards, even if it fails to load.
+        struct ForgetNamedObjects;
+
+        impl Drop for ForgetNamedObjects {
+            fn drop(&mut self) {
+                NAMED_OBJECTS_SO_FAR.with_borrow_mut(Vec::clear);
+            }
+        }
+
+        NAMED_OBJECTS_SO_FAR.with_borrow_mut(Vec::clear);
+        let _forget_named_objects = ForgetNamedObjects;
+        deserializer.deserialize_any(SceneVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPHERE: &str = r#"{ "type": "Sphere", "center": { "e": [0, 0, 0] }, "radius": 1, "mat": { "type": "Lambertian", "albedo": { "e": [0.5, 0.5, 0.5] } } }"#;
+
+    fn transform(object: &str, x: f64) -> String {
+        format!(r#"{{ "type": "Transform", "transforms": [{{ "translate": {{ "e": [{}, 0, 0] }} }}], {} }}"#, x, object)
+    }
+
+    #[test]
+    fn transforms_share_named_objects() {
+        let json = format!(
+            r#"{{ "named_objects": {{ "ball": {}, "two_balls": {{ "type": "Transform", "transforms": [], "named_object": "ball" }} }},
+                "objects": [{}, {}, {}] }}"#,
+            SPHERE,
+            transform(r#""named_object": "ball""#, 3.0),
+            transform(r#""named_object": "two_balls""#, -3.0),
+            transform(&format!(r#""object": {}"#, SPHERE), 6.0),
+        );
+        let scene: Scene = serde_json::from_str(&json).unwrap();
+        let NamedObjects(named) = &scene.named_objects;
+        // The table, two_balls, and the first object.
+        assert_eq!(Arc::strong_count(&named[0].1), 3);
+        assert_eq!(Arc::strong_count(&named[1].1), 2);
+        assert!(named_object("ball").is_none());
+
+        let written = serde_json::to_string(&scene).unwrap();
+        assert!(serde_json::from_str::<Scene>(&written).is_ok());
+    }
+
+    #[test]
+    fn missing_named_objects_are_errors() {
+        let used_before_named = format!(r#"{{ "objects": [{}], "named_objects": {{ "ball": {} }} }}"#, transform(r#""named_object": "ball""#, 0.0), SPHERE);
+        let both = format!(r#"{{ "named_objects": {{ "ball": {} }}, "objects": [{}] }}"#, SPHERE, transform(&format!(r#""named_object": "ball", "object": {}"#, SPHERE), 0.0));
+        let neither = r#"{ "objects": [{ "type": "Transform", "transforms": [] }] }"#.to_string();
+        let duplicate = format!(r#"{{ "named_objects": {{ "ball": {}, "ball": {} }}, "objects": [] }}"#, SPHERE, SPHERE);
+        for json in [used_before_named, both, neither, duplicate] {
+            assert!(serde_json::from_str::<Scene>(&json).is_err(), "{}", json);
+        }
+    }
+}
@@ -1,9 +1,13 @@
-use std::rc::Rc;
+use std::f64::consts::PI;
+use std::sync::Arc;
 
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
 
 use super::material::Material;
-use super::vec::Point3;
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
 use super::ray::Ray;
 use super::hit::{Hit, HitRecord};
 
@@ -11,23 +15,49 @@ use super::hit::{Hit, HitRecord};
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat: Rc<dyn Material>
+    mat: Arc<dyn Material>
 }
 
 impl Sphere {
     #[allow(unused)]
-    pub fn new(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Sphere {
+    pub fn new(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
             mat
         }
     }
+
+    pub fn center(&self) -> Point3 {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    // The same sphere, with the same material, just moved and resized.
+    pub fn with_center_and_radius(&self, center: Point3, radius: f64) -> Sphere {
+        Sphere::new(center, radius, self.mat.clone())
+    }
+
+    // The cosine of the angle between the middle and the edge of the cone
+    // that the sphere covers, as seen from origin, along with 1.0 minus that
+    // cosine (computed separately, since it gets tiny for far away spheres).
+    // None if origin is inside the sphere.
+    fn cone_from(&self, origin: Point3) -> Option<(f64, f64)> {
+        let sin_squared = self.radius * self.radius / (self.center - origin).length().powi(2);
+        if sin_squared >= 1.0 {
+            return None;
+        }
+        let cos_theta_max = (1.0 - sin_squared).sqrt();
+        Some((cos_theta_max, sin_squared / (1.0 + cos_theta_max)))
+    }
 }
 
 #[typetag::serde]
 impl Hit for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.origin() - self.center; // A - C
         let a = r.direction().length().powi(2); // b . b
         let half_b = oc.dot(r.direction()); // b * (A - C)
@@ -53,7 +83,7 @@ impl Hit for Sphere {
         }
         let p = r.at(root);
         let outward_normal = (p - self.center) / self.radius;
-        let rec = HitRecord::with_normal_against_ray(p, root, r, outward_normal, self.mat.clone());
+        let rec = HitRecord::with_normal_against_ray(p, root, r, outward_normal, self.mat.as_ref());
 
         Some(rec)
     }
@@ -61,4 +91,37 @@ impl Hit for Sphere {
     fn collides_with_sphere(&self, other: &Sphere) -> bool {
         (self.center - other.center).length() < (self.radius + other.radius)
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let r = Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn is_light(&self) -> bool {
+        self.mat.is_emissive()
+    }
+
+    // Picks uniformly from the cone of directions that hit the sphere.
+    fn sample_towards(&self, rng: &mut ChaCha12Rng, origin: Point3) -> Option<Vec3> {
+        let (_, one_minus_cos_theta_max) = self.cone_from(origin)?;
+        let w = (self.center - origin).normalized();
+        let (u, v) = w.orthonormal_basis();
+
+        let cos_theta = 1.0 - rng.gen::<f64>() * one_minus_cos_theta_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        Some(sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w)
+    }
+
+    fn pdf_towards(&self, origin: Point3, direction: Vec3) -> f64 {
+        match self.cone_from(origin) {
+            Some((cos_theta_max, one_minus_cos_theta_max))
+                if direction.normalized().dot((self.center - origin).normalized()) >= cos_theta_max =>
+            {
+                1.0 / (2.0 * PI * one_minus_cos_theta_max)
+            }
+            _ => 0.0,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use clap_serde_derive::clap::{self, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use super::vec::Color;
+
+// Ways of squishing radiance, which can be arbitrarily bright, into the 0.0 to
+// 1.0 range that 8-bit images can show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMapper {
+    // Leaves everything as is, so anything brighter than 1.0 gets clamped to
+    // flat white.
+    #[default]
+    Clamp,
+    // L / (1 + L) on the luminance. Nothing ever quite reaches white.
+    Reinhard,
+    // Reinhard, but with a white point: that luminance, and anything
+    // brighter, maps to white.
+    ExtendedReinhard,
+    // Krzysztof Narkowicz's fit of the ACES filmic curve, applied to each
+    // channel separately. Punchier than Reinhard, and desaturates highlights
+    // the way film does.
+    Aces,
+}
+
+// Everything that happens to the averaged radiance before it's ready for an
+// 8-bit image (other than the gamma correction).
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub tone_mapper: ToneMapper,
+    // In stops (EV). Every +1.0 doubles the brightness before tone mapping.
+    pub exposure: f64,
+    // Only used by ToneMapper::ExtendedReinhard.
+    pub white_point: f64,
+}
+
+// Rec. 709 luminance.
+fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+// Scales the color so its luminance becomes new_luminance, keeping its hue.
+fn with_luminance(color: Color, new_luminance: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(color);
+    if l <= 0.0 {
+        color
+    } else {
+        color * (new_luminance(l) / l)
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, radiance: Color) -> Color {
+        let exposed = radiance * 2f64.powf(self.exposure);
+
+        match self.tone_mapper {
+            ToneMapper::Clamp => exposed,
+            ToneMapper::Reinhard => with_luminance(exposed, |l| l / (1.0 + l)),
+            ToneMapper::ExtendedReinhard => {
+                let white_squared = self.white_point * self.white_point;
+                with_luminance(exposed, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            ToneMapper::Aces => {
+                let aces = |x: f64| {
+                    let x = x.max(0.0);
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+                };
+                Color::new(aces(exposed.x()), aces(exposed.y()), aces(exposed.z()))
+            }
+        }
+    }
+}
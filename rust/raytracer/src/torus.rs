@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::roots::{solve_quadratic, solve_quartic};
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// A donut: every point within minor_radius of a circle of major_radius
+// around center, in the plane that axis is perpendicular to.
+#[derive(Serialize, Deserialize)]
+pub struct Torus {
+    center: Point3,
+    axis: Vec3,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Arc<dyn Material>,
+}
+
+impl Torus {
+    #[allow(unused)]
+    pub fn new(center: Point3, axis: Vec3, major_radius: f64, minor_radius: f64, mat: Arc<dyn Material>) -> Torus {
+        Torus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            mat,
+        }
+    }
+
+    // The closest point to p on the circle running through the middle of
+    // the torus.
+    fn closest_point_on_circle(&self, p: Point3) -> Point3 {
+        let axis = self.axis.normalized();
+        let from_center = p - self.center;
+        let outwards = from_center - from_center.dot(axis) * axis;
+        // Every point on the circle is the same distance from the axis, so
+        // any of them will do.
+        let outwards = if outwards.near_zero() { axis.orthonormal_basis().0 } else { outwards.normalized() };
+        self.center + self.major_radius * outwards
+    }
+}
+
+#[typetag::serde]
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let axis = self.axis.normalized();
+        let (big, small) = (self.major_radius, self.minor_radius);
+
+        // Everything here is done with a unit direction, so t is a distance.
+        let length = r.direction().length();
+        let d = r.direction() / length;
+        let mut o = r.origin() - self.center;
+
+        // The quartic's coefficients get huge when the ray starts far away,
+        // which ruins the precision of its roots. Starting the ray from where
+        // it enters the sphere around the torus keeps them small, and rays
+        // that miss that sphere can't hit the torus anyway.
+        let bounding_radius = big + small;
+        let [enter, exit] = solve_quadratic(1.0, o.dot(d), o.dot(o) - bounding_radius * bounding_radius)?;
+        if exit < t_min * length || enter > t_max * length {
+            return None;
+        }
+        let start = enter.max(0.0);
+        o += start * d;
+
+        // Measured from the center, with the axis as z, the torus is where
+        // (|x|^2 + big^2 - small^2)^2 = 4 * big^2 * (x_x^2 + x_y^2),
+        // which turns into a quartic in t with x = o + t * d.
+        let s = o.dot(d);
+        let (o_axis, d_axis) = (o.dot(axis), d.dot(axis));
+        let k = o.dot(o) + big * big - small * small;
+        let four_big_squared = 4.0 * big * big;
+        let roots = solve_quartic(
+            4.0 * s,
+            4.0 * s * s + 2.0 * k - four_big_squared * (1.0 - d_axis * d_axis),
+            4.0 * s * k - 2.0 * four_big_squared * (s - o_axis * d_axis),
+            k * k - four_big_squared * (o.dot(o) - o_axis * o_axis),
+        );
+
+        let t = roots.into_iter().map(|root| (start + root) / length).find(|&t| t >= t_min && t <= t_max)?;
+        let p = r.at(t);
+        let outward_normal = (p - self.closest_point_on_circle(p)) / small;
+        Some(HitRecord::with_normal_against_ray(p, t, r, outward_normal, self.mat.as_ref()))
+    }
+
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        (self.closest_point_on_circle(other.center()) - other.center()).length() < self.minor_radius + other.radius()
+    }
+
+    // The circle through the middle of the torus reaches major_radius times
+    // the sine of the angle between each axis and the torus' axis (like a
+    // Disk), and the tube goes minor_radius past that.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = self.axis.normalized();
+        let mut extent = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            extent[i] = self.major_radius * (1.0 - axis[i] * axis[i]).max(0.0).sqrt() + self.minor_radius;
+        }
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
@@ -0,0 +1,217 @@
+use std::io;
+use std::sync::Arc;
+
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::matrix::Mat4;
+use super::ray::Ray;
+use super::roots::largest_cubic_root;
+use super::scene::named_object;
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// One step of a Transform, written in scene files like {"translate": ...},
+// {"rotate": {"axis": ..., "angle": ...}}, {"scale": ...}, or, for anything
+// else, {"matrix": ...}.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TransformStep {
+    Translate(Vec3),
+    // angle is in degrees, counterclockwise when looking down axis.
+    Rotate { axis: Vec3, angle: f64 },
+    // Each axis separately. Negative factors mirror the object.
+    Scale(Vec3),
+    Matrix(Mat4),
+}
+
+impl TransformStep {
+    fn matrix(&self) -> Mat4 {
+        match *self {
+            TransformStep::Translate(offset) => Mat4::translation(offset),
+            TransformStep::Rotate { axis, angle } => Mat4::rotation(axis, angle),
+            TransformStep::Scale(factors) => Mat4::scaling(factors),
+            TransformStep::Matrix(matrix) => matrix,
+        }
+    }
+}
+
+// What a Transform looks like in a scene file.
+#[derive(Serialize, Deserialize)]
+struct TransformSettings {
+    // Done to the object in order, so the first one happens first, the way
+    // they read.
+    transforms: Vec<TransformStep>,
+    // Either the object itself, or the name of one of the scene's
+    // named_objects, which every Transform using it shares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    object: Option<Arc<dyn Hit>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    named_object: Option<String>,
+}
+
+// Moves, turns, stretches, or otherwise transforms another object, which is
+// behind an Arc so that the same object can be placed all over a scene
+// without copying it (see TransformSettings for how in scene files).
+//
+// Rather than transforming the object, rays get transformed into the object's
+// space, and whatever they hit there gets transformed back. Since the ray's
+// direction isn't normalized afterwards, t means the same thing in both.
+#[derive(Deserialize)]
+#[serde(try_from = "TransformSettings")]
+pub struct Transform {
+    settings: TransformSettings,
+    object: Arc<dyn Hit>,
+    to_world: Mat4,
+    to_object: Mat4,
+    // How much the transform into object space scales volumes by, for
+    // correcting light sampling odds (see pdf_towards).
+    object_volume_scale: f64,
+    // Normals have to stay perpendicular to the surface, which takes the
+    // inverse transpose of the transform, not the transform itself.
+    normal_to_world: Mat4,
+    bbox: Option<Aabb>,
+}
+
+impl TryFrom<TransformSettings> for Transform {
+    type Error = io::Error;
+
+    fn try_from(settings: TransformSettings) -> io::Result<Transform> {
+        let invalid_data = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let object = match (&settings.object, &settings.named_object) {
+            (Some(object), None) => object.clone(),
+            (None, Some(name)) => named_object(name).ok_or_else(|| {
+                invalid_data(&format!(
+                    "There's no object named {:?} (named objects have to come before anything that uses them).",
+                    name
+                ))
+            })?,
+            _ => return Err(invalid_data("A Transform needs either an object or a named_object, but not both.")),
+        };
+
+        let to_world = settings.transforms.iter().fold(Mat4::identity(), |matrix, step| step.matrix() * matrix);
+        if !to_world.is_affine() {
+            return Err(invalid_data("Transforms have to be affine: the bottom row of a matrix has to be 0 0 0 1."));
+        }
+        let to_object = to_world
+            .inverse()
+            .ok_or_else(|| invalid_data("The transform squashes the object flat (like scaling by 0), so it can't be undone."))?;
+
+        // The box around the transformed corners of the object's box.
+        let bbox = object.bounding_box().map(|bbox| {
+            let (min, max) = (bbox.min(), bbox.max());
+            let corners = (0..8).map(|i| {
+                let corner = Point3::new(
+                    if i & 1 == 0 { min.x() } else { max.x() },
+                    if i & 2 == 0 { min.y() } else { max.y() },
+                    if i & 4 == 0 { min.z() } else { max.z() },
+                );
+                let corner = to_world.transform_point(corner);
+                Aabb::new(corner, corner)
+            });
+            corners.reduce(|bbox, corner| bbox.surrounding(&corner)).unwrap()
+        });
+
+        let column = |i: usize| Vec3::new(to_object[0][i], to_object[1][i], to_object[2][i]);
+        let object_volume_scale = column(0).dot(column(1).cross(column(2))).abs();
+
+        Ok(Transform {
+            settings,
+            object,
+            to_world,
+            to_object,
+            object_volume_scale,
+            normal_to_world: to_object.transpose(),
+            bbox,
+        })
+    }
+}
+
+// Written back out the way it was read in, as steps, not as one matrix.
+impl Serialize for Transform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.settings.serialize(serializer)
+    }
+}
+
+impl Transform {
+    #[allow(unused)]
+    pub fn new(to_world: Mat4, object: Arc<dyn Hit>) -> io::Result<Transform> {
+        Transform::try_from(TransformSettings {
+            transforms: vec![TransformStep::Matrix(to_world)],
+            object: Some(object),
+            named_object: None,
+        })
+    }
+
+    // The most that the transform into object space stretches anything, in
+    // any direction: the largest singular value of its linear part, which is
+    // the square root of the largest eigenvalue of (its transpose times it).
+    fn largest_object_stretch(&self) -> f64 {
+        let column = |i: usize| Vec3::new(self.to_object[0][i], self.to_object[1][i], self.to_object[2][i]);
+        let [c0, c1, c2] = [column(0), column(1), column(2)];
+        // (Transpose times it)'s entries are the dot products of the columns,
+        // and its eigenvalues are the roots of its characteristic polynomial.
+        let [s00, s11, s22, s01, s02, s12] = [c0.dot(c0), c1.dot(c1), c2.dot(c2), c0.dot(c1), c0.dot(c2), c1.dot(c2)];
+        let trace = s00 + s11 + s22;
+        let minors = s00 * s11 - s01 * s01 + s00 * s22 - s02 * s02 + s11 * s22 - s12 * s12;
+        let determinant = s00 * (s11 * s22 - s12 * s12) - s01 * (s01 * s22 - s12 * s02) + s02 * (s01 * s12 - s11 * s02);
+        largest_cubic_root(-trace, minors, -determinant).max(0.0).sqrt()
+    }
+}
+
+#[typetag::serde]
+impl Hit for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let object_ray = Ray::new(self.to_object.transform_point(r.origin()), self.to_object.transform_vector(r.direction()));
+        let rec = self.object.hit(&object_ray, t_min, t_max)?;
+
+        // The normal stays on the same side of the ray, so front_face carries
+        // over.
+        Some(HitRecord {
+            p: r.at(rec.t),
+            normal: self.normal_to_world.transform_vector(rec.normal).normalized(),
+            ..rec
+        })
+    }
+
+    // The sphere gets transformed into the object's space instead, where it
+    // might be a stretched out ellipsoid, so this uses the sphere around
+    // that. It's exact unless the object is scaled differently along
+    // different axes, and then it only errs on the side of colliding.
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        let center = self.to_object.transform_point(other.center());
+        let radius = other.radius() * self.largest_object_stretch();
+        self.object.collides_with_sphere(&other.with_center_and_radius(center, radius))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    // The object picks a direction in its own space, which just gets
+    // transformed back like any other vector.
+    fn sample_towards(&self, rng: &mut ChaCha12Rng, origin: Point3) -> Option<Vec3> {
+        let direction = self.object.sample_towards(rng, self.to_object.transform_point(origin))?;
+        Some(self.to_world.transform_vector(direction).normalized())
+    }
+
+    // Transforming directions squeezes some parts of the sphere of them
+    // together and spreads others out, unless it's only rotating and scaling
+    // evenly, so the object's odds per steradian have to be corrected by how
+    // much. For a unit direction d, a little patch of directions around it
+    // ends up |det| / |to_object * d|^3 times as big in object space.
+    fn pdf_towards(&self, origin: Point3, direction: Vec3) -> f64 {
+        let object_direction = self.to_object.transform_vector(direction.normalized());
+        let stretch = object_direction.length();
+        let pdf = self.object.pdf_towards(self.to_object.transform_point(origin), object_direction);
+        pdf * self.object_volume_scale / (stretch * stretch * stretch)
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Material;
+use super::ray::Ray;
+use super::sphere::Sphere;
+use super::vec::{Point3, Vec3};
+
+// The vertices go counterclockwise when looking at the front of the triangle,
+// which is the same convention as OBJ files. normals are optional per-vertex
+// normals, which get blended across the triangle so that a mesh of flat
+// triangles can look smooth.
+#[derive(Serialize, Deserialize)]
+pub struct Triangle {
+    vertices: [Point3; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    normals: Option<[Vec3; 3]>,
+    mat: Arc<dyn Material>,
+}
+
+impl Triangle {
+    #[allow(unused)]
+    pub fn new(vertices: [Point3; 3], normals: Option<[Vec3; 3]>, mat: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices,
+            normals,
+            mat,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, u, v) = intersect_triangle(r, t_min, t_max, self.vertices)?;
+        Some(triangle_hit_record(r, t, u, v, self.vertices, self.normals, self.mat.as_ref()))
+    }
+
+    fn collides_with_sphere(&self, other: &Sphere) -> bool {
+        triangle_collides_with_sphere(self.vertices, other)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(self.vertices))
+    }
+}
+
+// These are shared with Mesh, whose triangles are just indices into its
+// vertices, not Triangles.
+
+// The Möller-Trumbore algorithm. Returns t, along with the barycentric
+// coordinates u and v of the hit: it's at (1 - u - v) * p0 + u * p1 + v * p2.
+pub fn intersect_triangle(r: &Ray, t_min: f64, t_max: f64, [p0, p1, p2]: [Point3; 3]) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction().cross(edge2);
+    let determinant = edge1.dot(pvec);
+    // The ray is parallel to the triangle (or the triangle has no area). The
+    // determinant scales with the size of the triangle and the length of the
+    // ray's direction, so the cutoff does too, or tiny triangles would never
+    // get hit.
+    if determinant.abs() <= 1e-12 * edge1.length() * edge2.length() * r.direction().length() {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let tvec = r.origin() - p0;
+    let u = tvec.dot(pvec) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let v = r.direction().dot(qvec) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inverse_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, u, v))
+}
+
+// Which side is the front comes from the winding of the vertices, even when
+// there are vertex normals, so that it's the same all over the triangle. The
+// blended normal is only used for shading, turned to the same side.
+pub fn triangle_hit_record<'a>(
+    r: &Ray,
+    t: f64,
+    u: f64,
+    v: f64,
+    [p0, p1, p2]: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    mat: &'a dyn Material,
+) -> HitRecord<'a> {
+    let outward_normal = (p1 - p0).cross(p2 - p0).normalized();
+    let mut rec = HitRecord::with_normal_against_ray(r.at(t), t, r, outward_normal, mat);
+
+    if let Some([n0, n1, n2]) = normals {
+        let shading_normal = ((1.0 - u - v) * n0 + u * n1 + v * n2).normalized();
+        rec.normal = if shading_normal.dot(rec.normal) < 0.0 { -1.0 * shading_normal } else { shading_normal };
+    }
+
+    rec
+}
+
+pub fn triangle_bounding_box([p0, p1, p2]: [Point3; 3]) -> Aabb {
+    Aabb::new(p0, p0).surrounding(&Aabb::new(p1, p1)).surrounding(&Aabb::new(p2, p2)).padded(1e-4)
+}
+
+pub fn triangle_collides_with_sphere(vertices: [Point3; 3], other: &Sphere) -> bool {
+    (closest_point_on_triangle(other.center(), vertices) - other.center()).length() < other.radius()
+}
+
+// From Christer Ericson's Real-Time Collision Detection, section 5.1.5:
+// figures out which of the triangle's corners, edges, or face p is closest
+// to, then projects p onto that.
+fn closest_point_on_triangle(p: Point3, [a, b, c]: [Point3; 3]) -> Point3 {
+    let ab = b - a;
+    let ac = c - a;
+
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + d1 / (d1 - d3) * ab;
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + d2 / (d2 - d6) * ac;
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (d4 - d3) / ((d4 - d3) + (d5 - d6)) * (c - b);
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    a + vb * denominator * ab + vc * denominator * ac
+}
@@ -1,8 +1,9 @@
 use std::ops::{Index, IndexMut, Add, AddAssign, Sub, SubAssign, Mul, MulAssign, Div, DivAssign, Range};
+use std::str::FromStr;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Deserialize, Serialize, Default)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Default)]
 pub struct Vec3 {
     e: [f64; 3]
 }
@@ -19,6 +20,26 @@ impl Display for Vec3 {
     }
 }
 
+// Lets clap parse vectors from the command line, written as "x,y,z".
+impl FromStr for Vec3 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Vec3, String> {
+        let components = s
+            .split(',')
+            .map(|component| {
+                component.trim().parse::<f64>()
+                    .map_err(|err| format!("{:?} is not a number: {}", component, err))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        match components[..] {
+            [x, y, z] => Ok(Vec3::new(x, y, z)),
+            _ => Err(format!("Expected 3 comma-separated components, found {}.", components.len())),
+        }
+    }
+}
+
 impl Vec3 {
     pub fn new(e0: f64, e1: f64, e2: f64) -> Vec3 {
         Vec3 {
@@ -41,6 +62,15 @@ impl Vec3 {
         }
     }
 
+    pub fn random_in_unit_disk(rng: &mut impl Rng) -> Vec3 {
+        loop {
+            let v = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+            if v.length() < 1.0 { // The vector is within the unit disk, on the xy plane.
+                return v;
+            }
+        }
+    }
+
     pub fn random_in_hemisphere(rng: &mut impl Rng, initial_direction: Vec3) -> Vec3 {
         let in_unit_sphere = Vec3::random_in_unit_sphere(rng);
 
@@ -225,17 +255,37 @@ impl Vec3 {
     pub fn reflect(self, n: Vec3) -> Vec3 {
         self - 2.0 * self.dot(n) * n
     }
+
+    // Two unit vectors that, along with self (which must be a unit vector
+    // too), make an orthonormal basis.
+    pub fn orthonormal_basis(self) -> (Vec3, Vec3) {
+        // Any vector that isn't close to parallel with self will do.
+        let a = if self.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = self.cross(a).normalized();
+        let u = self.cross(v);
+        (u, v)
+    }
+
+    // Snell's law. self must be a unit vector, and n must be a unit normal on
+    // the same side of the surface as self comes from.
+    pub fn refract(self, n: Vec3, etai_over_etat: f64) -> Vec3 {
+        let cos_theta = (-1.0 * self).dot(n).min(1.0);
+        let r_out_perp = etai_over_etat * (self + cos_theta * n);
+        let r_out_parallel = -(1.0 - r_out_perp.dot(r_out_perp)).abs().sqrt() * n;
+        r_out_perp + r_out_parallel
+    }
 }
 
 // Color specific utility functions:
 
 impl Vec3 {
-    pub fn format_color(self, samples_per_pixel: u64) -> String {
-        format!(
-            "{} {} {}",
-            (256.0 * (self[0] / samples_per_pixel as f64).sqrt().clamp(0.0, 0.999)) as u64,
-            (256.0 * (self[1] / samples_per_pixel as f64).sqrt().clamp(0.0, 0.999)) as u64,
-            (256.0 * (self[2] / samples_per_pixel as f64).sqrt().clamp(0.0, 0.999)) as u64,
-        )
+    // Squishes an already encoded color (see color.rs) into 8 bits per
+    // channel, clamping anything outside of 0.0 to 1.0.
+    pub fn to_rgb8(self) -> [u8; 3] {
+        [
+            (256.0 * self[0].clamp(0.0, 0.999)) as u8,
+            (256.0 * self[1].clamp(0.0, 0.999)) as u8,
+            (256.0 * self[2].clamp(0.0, 0.999)) as u8,
+        ]
     }
 }
\ No newline at end of file